@@ -0,0 +1,73 @@
+//! Time writing import libraries of a 50k export DLL, and building its GNU
+//! export members with and without templates
+//!
+//! Run with `cargo bench`.
+
+use std::fmt::Write as _;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+use implib::def::ModuleDef;
+use implib::{Flavor, ImportLibrary, MachineType};
+
+const EXPORTS: usize = 50_000;
+const RUNS: usize = 5;
+
+fn def() -> String {
+    let mut def = String::from("LIBRARY bench.dll\nEXPORTS\n");
+    for i in 0..EXPORTS {
+        match i % 10 {
+            0 => writeln!(def, "bench_data_{} DATA", i),
+            1 => writeln!(def, "bench_ordinal_{} @{} NONAME", i, i),
+            _ => writeln!(def, "bench_function_with_a_longer_name_{}", i),
+        }
+        .unwrap();
+    }
+    def
+}
+
+fn bench(name: &str, def: &str, machine: MachineType, flavor: Flavor) {
+    let import_lib = ImportLibrary::new(def, machine, flavor).unwrap();
+    let mut times = Vec::new();
+    let mut size = 0;
+    for _ in 0..RUNS {
+        let mut lib = Cursor::new(Vec::new());
+        let start = Instant::now();
+        import_lib.write_to(&mut lib).unwrap();
+        times.push(start.elapsed());
+        size = lib.into_inner().len();
+    }
+    times.sort();
+    let median: Duration = times[RUNS / 2];
+    println!(
+        "{:<14} {:>10.1?} median of {} runs, {} bytes",
+        name, median, RUNS, size
+    );
+}
+
+fn bench_members(name: &str, def: &str, machine: MachineType, templates: bool) {
+    let def = ModuleDef::parse(def, machine).unwrap();
+    let mut times = Vec::new();
+    let mut size = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        size = implib::bench_export_members(&def, machine, templates).unwrap();
+        times.push(start.elapsed());
+    }
+    times.sort();
+    let median: Duration = times[RUNS / 2];
+    println!(
+        "{:<14} {:>10.1?} median of {} runs, {} bytes",
+        name, median, RUNS, size
+    );
+}
+
+fn main() {
+    let def = def();
+    bench("gnu amd64", &def, MachineType::AMD64, Flavor::Gnu);
+    bench("gnu i386", &def, MachineType::I386, Flavor::Gnu);
+    bench("gnu arm64", &def, MachineType::ARM64, Flavor::Gnu);
+    bench("msvc amd64", &def, MachineType::AMD64, Flavor::Msvc);
+    bench_members("make_one", &def, MachineType::AMD64, false);
+    bench_members("templates", &def, MachineType::AMD64, true);
+}
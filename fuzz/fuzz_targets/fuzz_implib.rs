@@ -1,31 +1,33 @@
 #![no_main]
-use std::io::{Cursor, ErrorKind};
+use std::io::Cursor;
 
 use libfuzzer_sys::fuzz_target;
-use implib::{ImportLibrary, MachineType, Flavor};
+use implib::{Error, ImportLibrary, MachineType, Flavor};
 
-fuzz_target!(|data: &str| {
-    let implib = match ImportLibrary::new(data, MachineType::AMD64, Flavor::Msvc) {
+const MACHINES: [MachineType; 6] = [
+    MachineType::I386,
+    MachineType::AMD64,
+    MachineType::ARMNT,
+    MachineType::ARM64,
+    MachineType::ARM64EC,
+    MachineType::ARM64X,
+];
+
+fn write(result: Result<ImportLibrary, Error>) {
+    let implib = match result {
         Ok(implib) => implib,
-        Err(err) => {
-            if err.kind() == ErrorKind::InvalidInput {
-                return;
-            }
-            panic!("{}", err);
-        }
+        Err(Error::Parse { .. } | Error::Validation(_)) => return,
+        Err(err) => panic!("{}", err),
     };
     let mut buf = Cursor::new(Vec::new());
     let _ = implib.write_to(&mut buf);
+}
 
-    let implib = match ImportLibrary::new(data, MachineType::AMD64, Flavor::Gnu) {
-        Ok(implib) => implib,
-        Err(err) => {
-            if err.kind() == ErrorKind::InvalidInput {
-                return;
-            }
-            panic!("{}", err);
+fuzz_target!(|data: &str| {
+    for flavor in [Flavor::Msvc, Flavor::Gnu] {
+        for machine in MACHINES {
+            write(ImportLibrary::new(data, machine, flavor));
         }
-    };
-    let mut buf = Cursor::new(Vec::new());
-    let _ = implib.write_to(&mut buf);
+        write(ImportLibrary::new_arm64x(data, data, flavor));
+    }
 });
@@ -0,0 +1,301 @@
+//! llvm-dlltool compatible command line
+
+use std::fs;
+use std::io::{BufWriter, Error, ErrorKind};
+
+use implib::def::ModuleDef;
+use implib::{Flavor, ImportLibrary, MachineType};
+
+const USAGE: &str = "Usage: implib [options]
+
+Options:
+  -D, --dllname <name>           Name of the DLL to import from
+  -d, --input-def <file>         Module definition file to read
+  -l, --output-lib <file>        Import library to write
+  -m, --machine <machine>        i386, i386:x86-64, arm, arm64, arm64ec or arm64x
+  -N, --input-native-def <file>  Module definition of the native ARM64 exports
+                                 of an ARM64X library
+  -k, --kill-at                  Remove @<n> from exported names
+  -h, --help                     Print this help";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    dll_name: Option<String>,
+    input_def: Option<String>,
+    output_lib: Option<String>,
+    machine: Option<MachineType>,
+    native_def: Option<String>,
+    kill_at: bool,
+    help: bool,
+}
+
+/// Run the llvm-dlltool dialect with the arguments after the program name
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let options = parse_args(args)?;
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let input_def = options
+        .input_def
+        .ok_or_else(|| invalid_input("no input file specified"))?;
+    let output_lib = options
+        .output_lib
+        .ok_or_else(|| invalid_input("no output file specified"))?;
+    let machine = options.machine.unwrap_or_else(default_machine);
+
+    let read = |path: &str| {
+        fs::read_to_string(path).map_err(|err| Error::new(err.kind(), format!("{}: {}", path, err)))
+    };
+    let mut def = ModuleDef::parse(&read(&input_def)?, machine)?;
+    if let Some(dll_name) = options.dll_name {
+        def.import_name = dll_name;
+    }
+    if def.import_name.is_empty() {
+        return Err(invalid_input("no DLL name specified"));
+    }
+    if machine == MachineType::I386 {
+        mingw_mangle(&mut def);
+        if options.kill_at {
+            kill_at(&mut def);
+        }
+        mingw_import_names(&mut def);
+    }
+
+    let import_lib = match options.native_def {
+        Some(native_def) => {
+            if !matches!(machine, MachineType::ARM64EC | MachineType::ARM64X) {
+                return Err(invalid_input(
+                    "native definitions are only supported for arm64ec and arm64x",
+                ));
+            }
+            let native_def = ModuleDef::parse(&read(&native_def)?, MachineType::ARM64)?;
+            ImportLibrary::from_defs(def, Some(native_def), MachineType::ARM64X, Flavor::Msvc)
+        }
+        None => ImportLibrary::from_def(def, machine, Flavor::Msvc),
+    };
+    let file = fs::File::create(&output_lib)
+        .map_err(|err| Error::new(err.kind(), format!("{}: {}", output_lib, err)))?;
+    Ok(import_lib.write_to(&mut BufWriter::new(file))?)
+}
+
+fn parse_args(args: &[String]) -> Result<Options, Error> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = split_flag(arg)?;
+        let mut value = || match inline_value {
+            Some(value) => Ok(value.to_string()),
+            None => args
+                .next()
+                .cloned()
+                .ok_or_else(|| invalid_input(format!("missing argument to {}", flag))),
+        };
+        match flag {
+            "-D" | "--dllname" => options.dll_name = Some(value()?),
+            "-d" | "--input-def" => options.input_def = Some(value()?),
+            "-l" | "--output-lib" => options.output_lib = Some(value()?),
+            "-m" | "--machine" => options.machine = Some(value()?.parse()?),
+            "-N" | "--input-native-def" => options.native_def = Some(value()?),
+            "-k" | "--kill-at" if inline_value.is_none() => options.kill_at = true,
+            "-h" | "--help" if inline_value.is_none() => options.help = true,
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    Ok(options)
+}
+
+/// Split `--flag=value` and `-fvalue` into the flag and its value
+fn split_flag(arg: &str) -> Result<(&str, Option<&str>), Error> {
+    if let Some(long) = arg.strip_prefix("--") {
+        Ok(match long.split_once('=') {
+            Some((name, value)) => (&arg[..name.len() + 2], Some(value)),
+            None => (arg, None),
+        })
+    } else if arg.starts_with('-') && arg.len() > 2 && arg.is_char_boundary(2) {
+        Ok((&arg[..2], Some(&arg[2..])))
+    } else if arg.starts_with('-') && arg.len() == 2 {
+        Ok((arg, None))
+    } else {
+        Err(invalid_input(format!("unknown argument: {}", arg)))
+    }
+}
+
+/// Machine of the host, like llvm-dlltool's default target
+fn default_machine() -> MachineType {
+    if cfg!(target_arch = "x86") {
+        MachineType::I386
+    } else if cfg!(target_arch = "arm") {
+        MachineType::ARMNT
+    } else if cfg!(target_arch = "aarch64") {
+        MachineType::ARM64
+    } else {
+        MachineType::AMD64
+    }
+}
+
+/// Import stdcall functions without their `@<n>` suffix
+fn kill_at(def: &mut ModuleDef) {
+    for export in &mut def.exports {
+        if let Some(ext_name) = export.ext_name.take() {
+            export.name = ext_name;
+        }
+        if !export.alias_target.is_empty() || export.name.starts_with('?') {
+            continue;
+        }
+        // Fastcall names start with `@`, skip it when looking for the
+        // decoration.
+        let at = export.name.char_indices().skip(1).find(|&(_, c)| c == '@');
+        if let Some((at, _)) = at {
+            export.symbol_name = export.name.clone();
+            export.name.truncate(at);
+        }
+    }
+}
+
+/// Whether `name` is decorated by the rules of MinGW def files, which
+/// unlike MSVC's take stdcall names such as `foo@4` as undecorated
+fn is_mingw_decorated(name: &str) -> bool {
+    name.starts_with(['@', '?']) || name.contains("@@")
+}
+
+/// Prefix the stdcall names the def parser leaves alone with `_`, like
+/// llvm-dlltool parsing MinGW def files
+fn mingw_mangle(def: &mut ModuleDef) {
+    let mangle = |name: &mut String| {
+        if name.contains('@') && !is_mingw_decorated(name) {
+            name.insert(0, '_');
+        }
+    };
+    for export in &mut def.exports {
+        // A forwarder string isn't a symbol.
+        if export.forward.is_none() {
+            mangle(&mut export.name);
+        }
+        if let Some(ext_name) = export.ext_name.as_mut() {
+            mangle(ext_name);
+        }
+    }
+}
+
+/// Import stdcall functions without their leading `_`, like MinGW
+///
+/// The MSVC writer imports names such as `_foo@4` as is, so the name in the
+/// DLL is spelled out as the import name.
+fn mingw_import_names(def: &mut ModuleDef) {
+    for export in &mut def.exports {
+        let has_import_name = export.no_name
+            || !export.symbol_name.is_empty()
+            || !export.alias_target.is_empty()
+            || export.export_as.is_some();
+        if has_import_name || !(export.name.starts_with('_') && export.name.contains('@')) {
+            continue;
+        }
+        let exported = export.ext_name.as_deref().unwrap_or(&export.name);
+        export.alias_target = exported
+            .strip_prefix(['?', '@', '_'])
+            .unwrap_or(exported)
+            .to_string();
+    }
+}
+
+fn invalid_input(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidInput, message.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, Error> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse(&[
+            "-m",
+            "i386:x86-64",
+            "-dfoo.def",
+            "--output-lib=foo.lib",
+            "--dllname",
+            "foo.dll",
+            "-k",
+        ])
+        .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                dll_name: Some("foo.dll".to_string()),
+                input_def: Some("foo.def".to_string()),
+                output_lib: Some("foo.lib".to_string()),
+                machine: Some(MachineType::AMD64),
+                kill_at: true,
+                ..Default::default()
+            }
+        );
+
+        let options = parse(&["--machine=arm64ec", "-N", "native.def"]).unwrap();
+        assert_eq!(options.machine, Some(MachineType::ARM64EC));
+        assert_eq!(options.native_def.as_deref(), Some("native.def"));
+
+        assert!(parse(&["-m", "mips"]).is_err());
+        assert!(parse(&["-d"]).is_err());
+        assert!(parse(&["--kill-at=yes"]).is_err());
+        assert!(parse(&["foo.def"]).is_err());
+    }
+
+    #[test]
+    fn test_kill_at() {
+        let mut def = ModuleDef::parse(
+            "EXPORTS\nfoo@4\n@bar@8\n?baz@@YAXXZ\nqux\nrenamed@4 = internal@4\néfoo@4\n",
+            MachineType::I386,
+        )
+        .unwrap();
+        kill_at(&mut def);
+        let names: Vec<(&str, &str)> = def
+            .exports
+            .iter()
+            .map(|e| (e.name.as_str(), e.symbol_name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("foo", "foo@4"),
+                ("@bar", "@bar@8"),
+                ("?baz@@YAXXZ", ""),
+                ("_qux", ""),
+                ("renamed", "renamed@4"),
+                ("éfoo", "éfoo@4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mingw_mangle() {
+        let mut def = ModuleDef::parse(
+            "EXPORTS\nfoo@4\n@bar@8\n?baz@@YAXXZ\nqux\n_quux@4\nnew@4 = old@4\n",
+            MachineType::I386,
+        )
+        .unwrap();
+        mingw_mangle(&mut def);
+        mingw_import_names(&mut def);
+        let names: Vec<(&str, &str)> = def
+            .exports
+            .iter()
+            .map(|e| (e.name.as_str(), e.alias_target.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("_foo@4", "foo@4"),
+                ("@bar@8", ""),
+                ("?baz@@YAXXZ", ""),
+                ("_qux", ""),
+                ("__quux@4", "_quux@4"),
+                ("_old@4", "new@4"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! lib.exe compatible command line, `/DEF` mode only
+
+use std::fs;
+use std::io::{BufWriter, Error, ErrorKind};
+use std::path::Path;
+
+use implib::def::{ConflictPolicy, ModuleDef, ShortExport};
+use implib::{Flavor, ImportLibrary, MachineType};
+
+const USAGE: &str = "Usage: implib lib /DEF[:file] [options] [objects]
+
+Options:
+  /DEF[:file]              Module definition file to read
+  /DEFARM64NATIVE:file     Module definition of the native ARM64 exports of
+                           an ARM64X library
+  /EXPORT:name[=internal][,@ordinal[,NONAME]][,DATA][,CONSTANT][,PRIVATE]
+                           Add an export
+  /MACHINE:machine         X86, X64, ARM, ARM64, ARM64EC or ARM64X
+  /NAME:name               Name of the DLL to import from
+  /OUT:file                Import library to write
+  /NOLOGO                  Ignored
+  @file                    Read further arguments from file
+
+Exports in the .drectve sections of COFF objects are added to the library.
+Switches are case insensitive and may start with / or -.";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    /// `Some("")` for `/DEF` without a file
+    def: Option<String>,
+    native_def: Option<String>,
+    exports: Vec<String>,
+    machine: Option<MachineType>,
+    name: Option<String>,
+    out: Option<String>,
+    /// COFF objects to collect `/EXPORT` directives from
+    objects: Vec<String>,
+    help: bool,
+}
+
+/// Run the lib.exe dialect with the arguments after the program name
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let args = expand_response_files(args, 0)?;
+    let options = parse_args(&args)?;
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let Some(def_path) = options.def else {
+        return Err(invalid_input("only /DEF mode is supported"));
+    };
+    let machine = options.machine.unwrap_or_else(|| {
+        eprintln!("implib: warning: /MACHINE not specified; defaulting to X86");
+        MachineType::I386
+    });
+
+    let read = |path: &str| {
+        fs::read_to_string(path).map_err(|err| Error::new(err.kind(), format!("{}: {}", path, err)))
+    };
+    let mut def = if def_path.is_empty() {
+        ModuleDef::default()
+    } else {
+        ModuleDef::parse(&read(&def_path)?, machine)?
+    };
+    for export in &options.exports {
+        def.exports.push(parse_export(export, machine)?);
+    }
+    if !options.objects.is_empty() {
+        let objects = options
+            .objects
+            .iter()
+            .map(|path| {
+                fs::read(path).map_err(|err| Error::new(err.kind(), format!("{}: {}", path, err)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let objects: Vec<&[u8]> = objects.iter().map(Vec::as_slice).collect();
+        let mut drectve = ModuleDef::from_drectve(&objects)?;
+        for export in &mut drectve.exports {
+            export.mangle(machine);
+        }
+        def = ModuleDef::merge(&[def, drectve], ConflictPolicy::Error)?.def;
+    }
+    if let Some(name) = options.name {
+        def.import_name = name;
+    }
+    if def.import_name.is_empty() {
+        return Err(invalid_input("no DLL name specified, use /NAME"));
+    }
+
+    // Like lib.exe, name the library after the module definition file or
+    // else the DLL.
+    let out = match options.out {
+        Some(out) => out,
+        None if !def_path.is_empty() => Path::new(&def_path)
+            .with_extension("lib")
+            .to_string_lossy()
+            .into_owned(),
+        None => Path::new(&def.import_name)
+            .with_extension("lib")
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    let import_lib = match options.native_def {
+        Some(native_def) => {
+            if !matches!(machine, MachineType::ARM64EC | MachineType::ARM64X) {
+                return Err(invalid_input(
+                    "/DEFARM64NATIVE requires /MACHINE:ARM64EC or /MACHINE:ARM64X",
+                ));
+            }
+            let native_def = ModuleDef::parse(&read(&native_def)?, MachineType::ARM64)?;
+            ImportLibrary::from_defs(def, Some(native_def), MachineType::ARM64X, Flavor::Msvc)
+        }
+        None => ImportLibrary::from_def(def, machine, Flavor::Msvc),
+    };
+    let file = fs::File::create(&out)
+        .map_err(|err| Error::new(err.kind(), format!("{}: {}", out, err)))?;
+    Ok(import_lib.write_to(&mut BufWriter::new(file))?)
+}
+
+fn parse_args(args: &[String]) -> Result<Options, Error> {
+    let mut options = Options::default();
+    for arg in args {
+        let Some(switch) = arg.strip_prefix(['/', '-']) else {
+            options.objects.push(arg.clone());
+            continue;
+        };
+        let (name, value) = match switch.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (switch, None),
+        };
+        let required = || {
+            value
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .ok_or_else(|| invalid_input(format!("missing argument to {}", arg)))
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "def" => options.def = Some(value.unwrap_or_default().to_string()),
+            "defarm64native" => options.native_def = Some(required()?),
+            "export" => options.exports.push(required()?),
+            "machine" => options.machine = Some(parse_machine(&required()?)?),
+            "name" => options.name = Some(required()?),
+            "out" => options.out = Some(required()?),
+            "nologo" if value.is_none() => {}
+            "help" | "?" if value.is_none() => options.help = true,
+            // Absolute paths on Unix look like switches, unlike switch names
+            // they have more slashes or an extension.
+            _ if arg.starts_with('/') && name.contains(['/', '.']) => {
+                options.objects.push(arg.clone())
+            }
+            _ => return Err(invalid_input(format!("unknown argument: {}", arg))),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_machine(machine: &str) -> Result<MachineType, Error> {
+    match machine.to_ascii_uppercase().as_str() {
+        "X86" | "I386" => Ok(MachineType::I386),
+        "X64" | "AMD64" => Ok(MachineType::AMD64),
+        "ARM" => Ok(MachineType::ARMNT),
+        "ARM64" => Ok(MachineType::ARM64),
+        "ARM64EC" => Ok(MachineType::ARM64EC),
+        "ARM64X" => Ok(MachineType::ARM64X),
+        _ => Err(invalid_input(format!("unknown machine: {}", machine))),
+    }
+}
+
+/// Parse a command line `/EXPORT`, mangling names like the def parser
+fn parse_export(arg: &str, machine: MachineType) -> Result<ShortExport, Error> {
+    let mut export = ShortExport::parse_directive(arg)?;
+    export.mangle(machine);
+    Ok(export)
+}
+
+/// Replace `@file` arguments with the arguments in the file
+fn expand_response_files(args: &[String], depth: usize) -> Result<Vec<String>, Error> {
+    if depth > 16 {
+        return Err(invalid_input("response files nested too deeply"));
+    }
+    let mut expanded = Vec::new();
+    for arg in args {
+        match arg.strip_prefix('@') {
+            Some(path) => {
+                let bytes = fs::read(path)
+                    .map_err(|err| Error::new(err.kind(), format!("{}: {}", path, err)))?;
+                let text = decode_response_file(&bytes)
+                    .ok_or_else(|| invalid_input(format!("{}: invalid encoding", path)))?;
+                expanded.extend(expand_response_files(
+                    &split_command_line(&text),
+                    depth + 1,
+                )?);
+            }
+            None => expanded.push(arg.clone()),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Response files written by MSVC tooling are often UTF-16 with a BOM
+fn decode_response_file(bytes: &[u8]) -> Option<String> {
+    if let Some(utf16) = bytes.strip_prefix(&[0xff, 0xfe]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+        return String::from_utf16(&units).ok();
+    }
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Split a command line with the Windows quoting rules: double quotes
+/// group, and backslashes only escape when followed by a double quote
+fn split_command_line(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut backslashes = 1;
+                while chars.next_if_eq(&'\\').is_some() {
+                    backslashes += 1;
+                }
+                if chars.peek() == Some(&'"') {
+                    arg.extend(std::iter::repeat_n('\\', backslashes / 2));
+                    if backslashes % 2 == 1 {
+                        arg.push('"');
+                        chars.next();
+                    }
+                } else {
+                    arg.extend(std::iter::repeat_n('\\', backslashes));
+                }
+                in_arg = true;
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            c => {
+                arg.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    args
+}
+
+fn invalid_input(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidInput, message.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, Error> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse(&[
+            "/nologo",
+            "/DEF:foo.def",
+            "-out:foo.lib",
+            "/Machine:x64",
+            "/NAME:foo.dll",
+            "/EXPORT:bar",
+            "-export:baz,@2,NONAME",
+        ])
+        .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                def: Some("foo.def".to_string()),
+                exports: vec!["bar".to_string(), "baz,@2,NONAME".to_string()],
+                machine: Some(MachineType::AMD64),
+                name: Some("foo.dll".to_string()),
+                out: Some("foo.lib".to_string()),
+                ..Default::default()
+            }
+        );
+
+        let options = parse(&["/DEF", "/DEFARM64NATIVE:native.def", "/MACHINE:ARM64X"]).unwrap();
+        assert_eq!(options.def.as_deref(), Some(""));
+        assert_eq!(options.native_def.as_deref(), Some("native.def"));
+        assert_eq!(options.machine, Some(MachineType::ARM64X));
+
+        assert!(parse(&["/MACHINE:MIPS"]).is_err());
+        assert!(parse(&["/OUT"]).is_err());
+        assert!(parse(&["-LTCG"]).is_err());
+        assert!(parse(&["/LTCG"]).is_err());
+        assert!(parse(&["/WX:NO"]).is_err());
+
+        let options = parse(&["foo.obj", "/tmp/bar.obj", "/baz.obj", "/DEF"]).unwrap();
+        assert_eq!(options.objects, ["foo.obj", "/tmp/bar.obj", "/baz.obj"]);
+    }
+
+    #[test]
+    fn test_parse_export() {
+        let export = parse_export("foo=bar,@3", MachineType::I386).unwrap();
+        assert_eq!(export.name, "_bar");
+        assert_eq!(export.ext_name.as_deref(), Some("_foo"));
+        assert_eq!(export.ordinal, 3);
+
+        let export = parse_export("HeapAlloc=NTDLL.RtlAllocateHeap", MachineType::I386).unwrap();
+        assert_eq!(export.name, "NTDLL.RtlAllocateHeap");
+        assert_eq!(export.ext_name.as_deref(), Some("_HeapAlloc"));
+
+        let export = parse_export("foo@4", MachineType::I386).unwrap();
+        assert_eq!(export.name, "foo@4");
+        let export = parse_export("foo", MachineType::AMD64).unwrap();
+        assert_eq!(export.name, "foo");
+    }
+
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(
+            split_command_line(
+                "/DEF:a.def  \"/OUT:my lib.lib\"\n/NAME:\"a b.dll\" \"\" x\\y \\\"q\\\\\"z\""
+            ),
+            [
+                "/DEF:a.def",
+                "/OUT:my lib.lib",
+                "/NAME:a b.dll",
+                "",
+                "x\\y",
+                "\"q\\z"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_response_file() {
+        assert_eq!(decode_response_file(b"/DEF").unwrap(), "/DEF");
+        assert_eq!(decode_response_file(b"\xef\xbb\xbf/DEF").unwrap(), "/DEF");
+        assert_eq!(
+            decode_response_file(b"\xff\xfe/\0D\0E\0F\0").unwrap(),
+            "/DEF"
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Command line tool generating Windows import libraries
+//!
+//! Speaks llvm-dlltool's flags by default and lib.exe's when invoked as
+//! `lib`/`llvm-lib` or as `implib lib`.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+mod dlltool;
+mod libexe;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args();
+    let program = args.next().unwrap_or_default();
+    let args: Vec<String> = args.collect();
+    let stem = Path::new(&program)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let result = if stem == "lib" || stem == "llvm-lib" {
+        libexe::run(&args)
+    } else if args.first().map(String::as_str) == Some("lib") {
+        libexe::run(&args[1..])
+    } else {
+        dlltool::run(&args)
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("implib: error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
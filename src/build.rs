@@ -0,0 +1,179 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+
+use std::path::{Path, PathBuf};
+
+use crate::def::ModuleDef;
+use crate::{Error, Flavor, ImportLibrary, MachineType};
+
+/// Generate an import library from the .def file at `def` for the target
+/// being built and link it, returning the library path
+///
+/// Call from a build script, see [`Build`].
+pub fn link_def(def: impl AsRef<Path>) -> Result<PathBuf, Error> {
+    Build::new(def).link()
+}
+
+/// Build script helper generating and linking an import library
+///
+/// The machine and flavor come from the `TARGET` triple. The library is written to `OUT_DIR` as
+/// `foo.lib` or `libfoo.dll.a`, named after the def file's `LIBRARY`, or
+/// the def file itself when there is none.
+#[derive(Debug, Clone)]
+pub struct Build {
+    def: PathBuf,
+    out_dir: Option<PathBuf>,
+}
+
+impl Build {
+    pub fn new(def: impl AsRef<Path>) -> Self {
+        Build {
+            def: def.as_ref().to_path_buf(),
+            out_dir: None,
+        }
+    }
+
+    /// Write the library to `out_dir` instead of `OUT_DIR`
+    pub fn out_dir(mut self, out_dir: impl AsRef<Path>) -> Self {
+        self.out_dir = Some(out_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Generate the import library and print the cargo directives linking it
+    pub fn link(&self) -> Result<PathBuf, Error> {
+        let stdout = io::stdout();
+        self.link_with(|key| env::var(key).ok(), &mut stdout.lock())
+    }
+
+    fn link_with(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+        out: &mut dyn Write,
+    ) -> Result<PathBuf, Error> {
+        let var = |key: &str| {
+            env(key).ok_or_else(|| {
+                Error::validation(format!("{} not set, not running in a build script?", key))
+            })
+        };
+        let target = var("TARGET")?;
+        let machine = MachineType::from_target_triple(&target)?;
+        let flavor = Flavor::from_target_triple(&target)?;
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => PathBuf::from(var("OUT_DIR")?),
+        };
+
+        let content = fs::read_to_string(&self.def)?;
+        let mut def = ModuleDef::parse(&content, machine)?;
+        if def.import_name.is_empty() {
+            let stem = self.def.file_stem().unwrap_or_default();
+            def.import_name = format!("{}.dll", stem.to_string_lossy());
+        }
+        let lib_name = link_name(&def.import_name).to_string();
+        let file_name = match flavor {
+            Flavor::Msvc => format!("{}.lib", lib_name),
+            Flavor::Gnu => format!("lib{}.dll.a", lib_name),
+        };
+        let path = out_dir.join(file_name);
+        let mut file = fs::File::create(&path)?;
+        ImportLibrary::from_def(def, machine, flavor).write_to(&mut file)?;
+
+        writeln!(out, "cargo:rerun-if-changed={}", self.def.display())?;
+        writeln!(out, "cargo:rustc-link-search=native={}", out_dir.display())?;
+        writeln!(out, "cargo:rustc-link-lib=dylib={}", lib_name)?;
+        Ok(path)
+    }
+}
+
+/// The DLL name without its `.dll` extension, as passed to the linker
+fn link_name(import_name: &str) -> &str {
+    match import_name.len().checked_sub(4) {
+        Some(at)
+            if import_name.is_char_boundary(at)
+                && import_name[at..].eq_ignore_ascii_case(".dll") =>
+        {
+            &import_name[..at]
+        }
+        _ => import_name,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn link(def: &str, target: &str) -> (Result<PathBuf, Error>, String) {
+        let dir = env::temp_dir().join(format!("implib-build-{}", target));
+        fs::create_dir_all(&dir).unwrap();
+        let def_path = dir.join("python3.def");
+        fs::write(&def_path, def).unwrap();
+        let vars = [
+            ("TARGET", target.to_string()),
+            ("OUT_DIR", dir.display().to_string()),
+        ];
+        let env = |key: &str| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone());
+        let mut out = Vec::new();
+        let result = Build::new(&def_path).link_with(env, &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_link() {
+        let def = "LIBRARY python39.dll\nEXPORTS\nPy_Initialize\n";
+        let (path, out) = link(def, "x86_64-pc-windows-msvc");
+        let path = path.unwrap();
+        assert_eq!(path.file_name().unwrap(), "python39.lib");
+        assert!(fs::metadata(&path).unwrap().len() > 0);
+        let dir = path.parent().unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "cargo:rerun-if-changed={}\n\
+                 cargo:rustc-link-search=native={}\n\
+                 cargo:rustc-link-lib=dylib=python39\n",
+                dir.join("python3.def").display(),
+                dir.display()
+            )
+        );
+
+        let (path, _) = link(def, "i686-pc-windows-gnu");
+        assert_eq!(path.unwrap().file_name().unwrap(), "libpython39.dll.a");
+
+        // Named after the def file without a LIBRARY statement
+        let (path, out) = link("EXPORTS\nPy_Initialize\n", "aarch64-pc-windows-msvc");
+        assert_eq!(path.unwrap().file_name().unwrap(), "python3.lib");
+        assert!(out.ends_with("cargo:rustc-link-lib=dylib=python3\n"));
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let def = "LIBRARY python39.dll\nEXPORTS\nPy_Initialize\n";
+        let (result, _) = link(def, "riscv64-pc-windows-msvc");
+        let err = result.unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{:?}", err);
+        assert_eq!(
+            err.to_string(),
+            "unsupported architecture riscv64 in target triple: riscv64-pc-windows-msvc"
+        );
+        let (result, _) = link(def, "x86_64-pc-windows-itanium");
+        let err = result.unwrap_err();
+        assert!(matches!(err, Error::Unsupported(_)), "{:?}", err);
+
+        let err = Build::new("python3.def")
+            .link_with(|_| None, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "TARGET not set, not running in a build script?"
+        );
+    }
+
+    #[test]
+    fn test_link_name() {
+        assert_eq!(link_name("python39.dll"), "python39");
+        assert_eq!(link_name("KERNEL32.DLL"), "KERNEL32");
+        assert_eq!(link_name("foo.drv"), "foo.drv");
+        assert_eq!(link_name("dll"), "dll");
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::{ModuleDef, ShortExport};
+
+/// Whether a change keeps existing consumers working
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Existing consumers keep linking and loading
+    Compatible,
+    /// Existing consumers may fail to link or load
+    Breaking,
+}
+
+/// Kind of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Code,
+    Data,
+    Constant,
+}
+
+impl ExportKind {
+    fn of(export: &ShortExport) -> Self {
+        if export.constant {
+            Self::Constant
+        } else if export.data {
+            Self::Data
+        } else {
+            Self::Code
+        }
+    }
+}
+
+/// A single difference between two module definitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The DLL name changed
+    DllName { old: String, new: String },
+    /// An export was added
+    Added { name: String },
+    /// An export was removed
+    Removed { name: String },
+    /// The ordinal of an export changed
+    Ordinal { name: String, old: u16, new: u16 },
+    /// An export switched between `NONAME` and exported by name
+    NoName { name: String, old: bool, new: bool },
+    /// An export switched between code, `DATA` and `CONSTANT`
+    Kind {
+        name: String,
+        old: ExportKind,
+        new: ExportKind,
+    },
+    /// The weak alias target (`foo == bar`) of an export changed
+    Alias {
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+/// A classified difference between two module definitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub compatibility: Compatibility,
+}
+
+impl Change {
+    pub fn is_breaking(&self) -> bool {
+        self.compatibility == Compatibility::Breaking
+    }
+}
+
+/// Differences between an old and a new module definition
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleDiff {
+    pub changes: Vec<Change>,
+}
+
+impl ModuleDiff {
+    /// Returns true if any change may break existing consumers
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(Change::is_breaking)
+    }
+
+    /// Iterate over the breaking changes
+    pub fn breaking(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(|change| change.is_breaking())
+    }
+}
+
+impl ModuleDef {
+    /// Compare this (old) module definition against a new one
+    ///
+    /// Exports are matched by their exported name, so internal names
+    /// (`foo = bar`) don't take part in the comparison.
+    pub fn diff(&self, new: &ModuleDef) -> ModuleDiff {
+        let mut changes = Vec::new();
+        if !self.import_name.eq_ignore_ascii_case(&new.import_name) {
+            changes.push(Change {
+                kind: ChangeKind::DllName {
+                    old: self.import_name.clone(),
+                    new: new.import_name.clone(),
+                },
+                compatibility: Compatibility::Breaking,
+            });
+        }
+
+        let old_exports = exports_by_name(self);
+        let new_exports = exports_by_name(new);
+        for (&name, old) in &old_exports {
+            match new_exports.get(name) {
+                Some(new) => diff_export(name, old, new, &mut changes),
+                None => changes.push(Change {
+                    kind: ChangeKind::Removed {
+                        name: name.to_string(),
+                    },
+                    compatibility: Compatibility::Breaking,
+                }),
+            }
+        }
+        for &name in new_exports.keys() {
+            if !old_exports.contains_key(name) {
+                changes.push(Change {
+                    kind: ChangeKind::Added {
+                        name: name.to_string(),
+                    },
+                    compatibility: Compatibility::Compatible,
+                });
+            }
+        }
+        ModuleDiff { changes }
+    }
+}
+
+fn exports_by_name(def: &ModuleDef) -> BTreeMap<&str, &ShortExport> {
+    let mut exports = BTreeMap::new();
+    for export in &def.exports {
+        exports.entry(export.exported_name()).or_insert(export);
+    }
+    exports
+}
+
+fn diff_export(name: &str, old: &ShortExport, new: &ShortExport, changes: &mut Vec<Change>) {
+    if old.ordinal != new.ordinal {
+        // Only consumers importing by ordinal notice, an ordinal that
+        // wasn't pinned before can't have been relied upon.
+        let compatibility = if (old.no_name || new.no_name) && old.ordinal != 0 {
+            Compatibility::Breaking
+        } else {
+            Compatibility::Compatible
+        };
+        changes.push(Change {
+            kind: ChangeKind::Ordinal {
+                name: name.to_string(),
+                old: old.ordinal,
+                new: new.ordinal,
+            },
+            compatibility,
+        });
+    }
+    if old.no_name != new.no_name {
+        // Consumers importing by name lose the name, consumers importing
+        // by ordinal keep working when the name shows up.
+        changes.push(Change {
+            kind: ChangeKind::NoName {
+                name: name.to_string(),
+                old: old.no_name,
+                new: new.no_name,
+            },
+            compatibility: if new.no_name {
+                Compatibility::Breaking
+            } else {
+                Compatibility::Compatible
+            },
+        });
+    }
+    let (old_kind, new_kind) = (ExportKind::of(old), ExportKind::of(new));
+    if old_kind != new_kind {
+        changes.push(Change {
+            kind: ChangeKind::Kind {
+                name: name.to_string(),
+                old: old_kind,
+                new: new_kind,
+            },
+            compatibility: Compatibility::Breaking,
+        });
+    }
+    if old.alias_target != new.alias_target {
+        let alias = |target: &str| (!target.is_empty()).then(|| target.to_string());
+        changes.push(Change {
+            kind: ChangeKind::Alias {
+                name: name.to_string(),
+                old: alias(&old.alias_target),
+                new: alias(&new.alias_target),
+            },
+            compatibility: Compatibility::Breaking,
+        });
+    }
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Compatible => f.write_str("compatible"),
+            Self::Breaking => f.write_str("breaking"),
+        }
+    }
+}
+
+impl fmt::Display for ExportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Code => f.write_str("code"),
+            Self::Data => f.write_str("data"),
+            Self::Constant => f.write_str("constant"),
+        }
+    }
+}
+
+/// Formats as tab separated fields: compatibility, change, export name
+/// and the old and new values where applicable.
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t", self.compatibility)?;
+        match &self.kind {
+            ChangeKind::DllName { old, new } => write!(f, "dll-name\t{}\t{}", old, new),
+            ChangeKind::Added { name } => write!(f, "added\t{}", name),
+            ChangeKind::Removed { name } => write!(f, "removed\t{}", name),
+            ChangeKind::Ordinal { name, old, new } => {
+                write!(f, "ordinal\t{}\t{}\t{}", name, old, new)
+            }
+            ChangeKind::NoName { name, old, new } => {
+                write!(f, "noname\t{}\t{}\t{}", name, old, new)
+            }
+            ChangeKind::Kind { name, old, new } => write!(f, "kind\t{}\t{}\t{}", name, old, new),
+            ChangeKind::Alias { name, old, new } => write!(
+                f,
+                "alias\t{}\t{}\t{}",
+                name,
+                old.as_deref().unwrap_or("-"),
+                new.as_deref().unwrap_or("-")
+            ),
+        }
+    }
+}
+
+/// Formats one change per line, see [`Change`]
+impl fmt::Display for ModuleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MachineType;
+
+    fn parse(def: &str) -> ModuleDef {
+        ModuleDef::parse(def, MachineType::AMD64).unwrap()
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let def = parse("LIBRARY foo.dll\nEXPORTS\nbar @1\nbaz DATA\n");
+        let diff = def.diff(&def);
+        assert!(diff.changes.is_empty());
+        assert!(!diff.is_breaking());
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let old = parse(
+            "LIBRARY foo.dll
+EXPORTS
+removed
+renamed_internal = a
+ordinal @1 NONAME
+unpinned
+by_name @2
+to_noname @3
+from_noname @4 NONAME
+to_data
+aliased == one
+",
+        );
+        let new = parse(
+            "LIBRARY FOO.DLL
+EXPORTS
+added
+renamed_internal = b
+ordinal @5 NONAME
+unpinned @6
+by_name @7
+to_noname @3 NONAME
+from_noname @4
+to_data DATA
+aliased == two
+",
+        );
+        let diff = old.diff(&new);
+        let lines: Vec<String> = diff.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "breaking\talias\taliased\tone\ttwo",
+                "compatible\tordinal\tby_name\t2\t7",
+                "compatible\tnoname\tfrom_noname\ttrue\tfalse",
+                "breaking\tordinal\tordinal\t1\t5",
+                "breaking\tremoved\tremoved",
+                "breaking\tkind\tto_data\tcode\tdata",
+                "breaking\tnoname\tto_noname\tfalse\ttrue",
+                "compatible\tordinal\tunpinned\t0\t6",
+                "compatible\tadded\tadded",
+            ]
+        );
+        assert!(diff.is_breaking());
+        assert_eq!(diff.breaking().count(), 5);
+    }
+
+    #[test]
+    fn test_diff_dll_name() {
+        let old = parse("LIBRARY foo.dll\nEXPORTS\nbar\n");
+        let new = parse("LIBRARY foo2.dll\nEXPORTS\nbar\nbaz\n");
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.to_string(),
+            "breaking\tdll-name\tfoo.dll\tfoo2.dll\ncompatible\tadded\tbaz\n"
+        );
+    }
+}
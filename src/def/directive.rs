@@ -0,0 +1,173 @@
+use object::{Object, ObjectSection};
+
+use super::{ConflictPolicy, Forward, ModuleDef, ShortExport};
+use crate::Error;
+
+impl ShortExport {
+    /// Parse the argument of an `/EXPORT` directive,
+    /// `name[=internal][,@ordinal[,NONAME]][,DATA][,CONSTANT][,PRIVATE]`
+    ///
+    /// Names are taken as is, unlike [`ModuleDef::parse`] no i386 mangling
+    /// is applied, see [`ShortExport::mangle`]. `name=other.target` is a
+    /// forwarder.
+    pub fn parse_directive(arg: &str) -> Result<ShortExport, Error> {
+        let err = || Error::validation(format!("invalid /EXPORT: {}", arg));
+        let mut parts = arg.split(',');
+        let name = parts.next().unwrap_or_default();
+        let mut export = match name.split_once('=') {
+            Some((ext_name, name)) if !ext_name.is_empty() && !name.is_empty() => ShortExport {
+                name: name.to_string(),
+                ext_name: Some(ext_name.to_string()),
+                forward: Forward::parse(name),
+                ..Default::default()
+            },
+            None if !name.is_empty() => ShortExport {
+                name: name.to_string(),
+                ..Default::default()
+            },
+            _ => return Err(err()),
+        };
+        for part in parts {
+            if part.eq_ignore_ascii_case("noname") {
+                if export.ordinal == 0 {
+                    return Err(err());
+                }
+                export.no_name = true;
+            } else if part.eq_ignore_ascii_case("data") {
+                export.data = true;
+            } else if part.eq_ignore_ascii_case("constant") {
+                export.constant = true;
+            } else if part.eq_ignore_ascii_case("private") {
+                export.private = true;
+            } else if let Some(ordinal) = part.strip_prefix('@') {
+                export.ordinal = ordinal
+                    .parse()
+                    .ok()
+                    .filter(|&ordinal| ordinal != 0)
+                    .ok_or_else(err)?;
+            } else {
+                return Err(err());
+            }
+        }
+        Ok(export)
+    }
+}
+
+impl ModuleDef {
+    /// Collect the `/EXPORT` directives in the `.drectve` sections of COFF
+    /// objects
+    ///
+    /// Exports repeated identically across objects are kept once, differing
+    /// ones are an error. The import name is left empty and names aren't
+    /// mangled, see [`ShortExport::mangle`].
+    pub fn from_drectve(objects: &[&[u8]]) -> Result<ModuleDef, Error> {
+        let mut fragments = Vec::with_capacity(objects.len());
+        for (index, data) in objects.iter().enumerate() {
+            let file = object::File::parse(*data)
+                .map_err(|e| Error::encoding(format!("object {}: {}", index, e)))?;
+            if file.format() != object::BinaryFormat::Coff {
+                return Err(Error::encoding(format!(
+                    "object {}: not a COFF object",
+                    index
+                )));
+            }
+            let mut fragment = ModuleDef::default();
+            for section in file.sections() {
+                if section.name_bytes() != Ok(b".drectve") {
+                    continue;
+                }
+                let data = section.data()?;
+                let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+                let directives = String::from_utf8_lossy(data);
+                for directive in split_directives(&directives) {
+                    let Some((name, value)) = directive
+                        .strip_prefix(['/', '-'])
+                        .and_then(|directive| directive.split_once(':'))
+                    else {
+                        continue;
+                    };
+                    if name.eq_ignore_ascii_case("export") {
+                        fragment.exports.push(ShortExport::parse_directive(value)?);
+                    }
+                }
+            }
+            fragments.push(fragment);
+        }
+        Ok(ModuleDef::merge(&fragments, ConflictPolicy::Error)?.def)
+    }
+}
+
+/// Split directives at whitespace, double quotes group and are removed
+fn split_directives(directives: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    for c in directives.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_arg = true;
+            }
+            c if (c.is_whitespace() || c == '\0') && !in_quotes => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            c => {
+                arg.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    args
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_directive() {
+        let export = ShortExport::parse_directive("foo").unwrap();
+        assert_eq!(export.name, "foo");
+        assert_eq!(export.ext_name, None);
+
+        let export = ShortExport::parse_directive("foo=bar,@3,NONAME,data,PRIVATE").unwrap();
+        assert_eq!(export.name, "bar");
+        assert_eq!(export.ext_name.as_deref(), Some("foo"));
+        assert_eq!(export.ordinal, 3);
+        assert!(export.no_name);
+        assert!(export.data);
+        assert!(export.private);
+
+        let export = ShortExport::parse_directive("HeapAlloc=NTDLL.RtlAllocateHeap").unwrap();
+        assert_eq!(export.name, "NTDLL.RtlAllocateHeap");
+        assert_eq!(export.forward.unwrap().dll, "NTDLL");
+
+        for bad in [
+            "",
+            "=foo",
+            "foo=",
+            "foo,NONAME",
+            "foo,@0",
+            "foo,@x",
+            "foo,BOGUS",
+        ] {
+            let err = ShortExport::parse_directive(bad).unwrap_err();
+            assert_eq!(err.to_string(), format!("invalid /EXPORT: {}", bad));
+        }
+    }
+
+    #[test]
+    fn test_split_directives() {
+        assert_eq!(
+            split_directives("  /DEFAULTLIB:\"LIBCMT\" /EXPORT:\"a b\",DATA\0"),
+            ["/DEFAULTLIB:LIBCMT", "/EXPORT:a b,DATA"]
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use object::pe::*;
+use object::read::archive::ArchiveFile;
+use object::{Object, ObjectSection, ObjectSymbol, SectionFlags, SectionIndex, SymbolSection};
+
+use super::{ModuleDef, ShortExport};
+use crate::{Error, MachineType};
+
+/// Symbols dlltool and lld never export automatically
+const EXCLUDE_SYMBOLS: &[&str] = &[
+    "__NULL_IMPORT_DESCRIPTOR",
+    "_pei386_runtime_relocator",
+    "do_pseudo_reloc",
+    "impure_ptr",
+    "_impure_ptr",
+    "_fmode",
+    "environ",
+    "__dso_handle",
+    "DllMain",
+    "DllEntryPoint",
+    "DllMainCRTStartup",
+];
+const EXCLUDE_SYMBOLS_I386: &[&str] = &[
+    "__NULL_IMPORT_DESCRIPTOR",
+    "__pei386_runtime_relocator",
+    "_do_pseudo_reloc",
+    "_impure_ptr",
+    "__impure_ptr",
+    "__fmode",
+    "_environ",
+    "___dso_handle",
+    "_DllMain@12",
+    "_DllEntryPoint@12",
+    "_DllMainCRTStartup@12",
+];
+const EXCLUDE_PREFIXES: &[&str] = &[
+    // Import symbols
+    "__imp_",
+    "__IMPORT_DESCRIPTOR_",
+    "_head_",
+    "__nm_",
+    // C++ runtime
+    "__rtti_",
+    "__builtin_",
+    // Artificial symbols such as .refptr.foo
+    ".",
+    // Profiling
+    "__profc_",
+    "__profd_",
+    "__profvp_",
+];
+const EXCLUDE_SUFFIXES: &[&str] = &["_iname", "_NULL_THUNK_DATA"];
+/// Runtime libraries, without the extension
+const EXCLUDE_LIBS: &[&str] = &[
+    "libgcc",
+    "libgcc_s",
+    "libstdc++",
+    "libmingw32",
+    "libmingwex",
+    "libg2c",
+    "libsupc++",
+    "libobjc",
+    "libgcj",
+    "libclang_rt.builtins",
+    "libclang_rt.builtins-aarch64",
+    "libclang_rt.builtins-arm",
+    "libclang_rt.builtins-i386",
+    "libclang_rt.builtins-x86_64",
+    "libunwind",
+    "libmsvcrt",
+    "libmsvcrt-os",
+    "libucrt",
+    "libucrtbase",
+    "libc++",
+    "libc++abi",
+];
+/// CRT startup objects
+const EXCLUDE_OBJECTS: &[&str] = &[
+    "crt0.o",
+    "crt1.o",
+    "crt1u.o",
+    "crt2.o",
+    "crt2u.o",
+    "dllcrt1.o",
+    "dllcrt2.o",
+    "gcrt0.o",
+    "gcrt1.o",
+    "gcrt2.o",
+    "crtbegin.o",
+    "crtend.o",
+];
+
+/// Options for [`ModuleDef::export_all`]
+#[derive(Debug, Clone, Default)]
+pub struct ExportAllOptions {
+    /// Symbols not to export, like dlltool's `--exclude-symbols`. Matched
+    /// against the symbol name and, on i386, the name without the leading
+    /// underscore.
+    pub exclude_symbols: Vec<String>,
+    /// Archives whose members aren't exported, by file name without the
+    /// extension, like dlltool's `--exclude-libs`
+    pub exclude_libs: Vec<String>,
+    /// Skip the default exclusion lists, like dlltool's
+    /// `--no-default-excludes`
+    pub no_default_excludes: bool,
+}
+
+/// A COFF object or archive member to collect symbols from
+struct Input<'a> {
+    data: &'a [u8],
+    /// Archive file name without the extension, empty for objects
+    lib_name: &'a str,
+    /// Object file name
+    object_name: &'a str,
+}
+
+impl ModuleDef {
+    /// Build a module definition exporting every global symbol defined in
+    /// COFF objects and archives, like dlltool's `--export-all-symbols`
+    ///
+    /// `inputs` are pairs of file name and contents. Symbols in executable
+    /// sections are exported as code, all others as `DATA`. Runtime
+    /// symbols, libraries and objects are excluded unless
+    /// [`ExportAllOptions::no_default_excludes`] is set. The import name is
+    /// left empty.
+    pub fn export_all(
+        inputs: &[(&str, &[u8])],
+        machine: MachineType,
+        options: &ExportAllOptions,
+    ) -> Result<ModuleDef, Error> {
+        let mut objects = Vec::new();
+        for &(path, data) in inputs {
+            let file_name = file_name(path);
+            if data.starts_with(b"!<arch>\n") {
+                let archive = ArchiveFile::parse(data).map_err(|e| invalid_data(path, e))?;
+                let lib_name = file_name
+                    .rsplit_once('.')
+                    .map_or(file_name, |(stem, _)| stem);
+                for member in archive.members() {
+                    let member = member.map_err(|e| invalid_data(path, e))?;
+                    let name = std::str::from_utf8(member.name()).unwrap_or_default();
+                    // Skip symbol tables and import members.
+                    if name.starts_with('/') || name.is_empty() {
+                        continue;
+                    }
+                    let data = member.data(data).map_err(|e| invalid_data(path, e))?;
+                    if data.starts_with(&[0, 0, 0xff, 0xff]) {
+                        continue;
+                    }
+                    objects.push(Input {
+                        data,
+                        lib_name,
+                        object_name: file_name_of(name),
+                    });
+                }
+            } else {
+                objects.push(Input {
+                    data,
+                    lib_name: "",
+                    object_name: file_name,
+                });
+            }
+        }
+
+        let excluded = |list: &[String], name: &str| list.iter().any(|item| item == name);
+        let mut candidates = Vec::new();
+        let mut imp_symbols = HashSet::new();
+        for input in &objects {
+            if !options.no_default_excludes {
+                if !input.lib_name.is_empty() && EXCLUDE_LIBS.contains(&input.lib_name) {
+                    continue;
+                }
+                if input.lib_name.is_empty() && EXCLUDE_OBJECTS.contains(&input.object_name) {
+                    continue;
+                }
+            }
+            if excluded(&options.exclude_libs, input.lib_name) {
+                continue;
+            }
+            let file =
+                object::File::parse(input.data).map_err(|e| invalid_data(input.object_name, e))?;
+            if file.format() != object::BinaryFormat::Coff {
+                return Err(Error::encoding(format!(
+                    "{}: not a COFF object",
+                    input.object_name
+                )));
+            }
+            for symbol in file.symbols() {
+                if !symbol.is_global() || symbol.is_undefined() {
+                    continue;
+                }
+                let Ok(name) = symbol.name() else {
+                    continue;
+                };
+                if let Some(name) = name.strip_prefix("__imp_") {
+                    imp_symbols.insert(name.to_string());
+                }
+                let data = match symbol.section() {
+                    SymbolSection::Section(index) => !is_executable(&file, index),
+                    SymbolSection::Common => true,
+                    _ => continue,
+                };
+                candidates.push((name.to_string(), data));
+            }
+        }
+
+        let mut def = ModuleDef::default();
+        let mut seen = HashSet::new();
+        for (name, data) in candidates {
+            let undecorated = match machine {
+                MachineType::I386 => name.strip_prefix('_').unwrap_or(&name),
+                _ => &name,
+            };
+            if !options.no_default_excludes && is_default_excluded(&name, machine) {
+                continue;
+            }
+            if excluded(&options.exclude_symbols, &name)
+                || excluded(&options.exclude_symbols, undecorated)
+                // Symbols with an import thunk are re-exports of an import.
+                || imp_symbols.contains(&name)
+                // Definitions repeated across objects, e.g. COMDATs
+                || !seen.insert(name.clone())
+            {
+                continue;
+            }
+            // The object symbol is already mangled, so it is the import
+            // symbol as is.
+            def.exports.push(ShortExport {
+                name,
+                data,
+                ..Default::default()
+            });
+        }
+        Ok(def)
+    }
+}
+
+fn is_default_excluded(name: &str, machine: MachineType) -> bool {
+    let symbols = match machine {
+        MachineType::I386 => EXCLUDE_SYMBOLS_I386,
+        _ => EXCLUDE_SYMBOLS,
+    };
+    // The i386 head symbols carry an extra underscore.
+    let prefix_name = match machine {
+        MachineType::I386 => name.strip_prefix('_').unwrap_or(name),
+        _ => name,
+    };
+    symbols.contains(&name)
+        || EXCLUDE_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix) || prefix_name.starts_with(prefix))
+        || EXCLUDE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+fn is_executable(file: &object::File<'_>, index: SectionIndex) -> bool {
+    match file.section_by_index(index).map(|section| section.flags()) {
+        Ok(SectionFlags::Coff { characteristics }) => {
+            characteristics & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) != 0
+        }
+        _ => false,
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+/// File name of an archive member, GNU archives terminate names with `/`
+fn file_name_of(member: &str) -> &str {
+    file_name(member.strip_suffix('/').unwrap_or(member))
+}
+
+fn invalid_data(path: &str, err: object::read::Error) -> Error {
+    Error::encoding(format!("{}: {}", path, err))
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use super::{ModuleDef, ShortExport};
+use crate::Error;
+
+/// How to resolve exports that collide while merging module definitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Fail on the first conflict
+    #[default]
+    Error,
+    /// Keep the export from the earlier fragment
+    FirstWins,
+    /// Keep the export from the later fragment
+    LastWins,
+}
+
+/// What two colliding exports have in common
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Same exported name with different attributes
+    Name(String),
+    /// Same ordinal on different exports
+    Ordinal(u16),
+}
+
+/// A conflict resolved while merging
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub kind: ConflictKind,
+    /// Index of the fragment the kept export came from
+    pub kept: usize,
+    /// Index of the fragment the dropped export came from
+    pub dropped: usize,
+}
+
+/// Result of [`ModuleDef::merge`]
+#[derive(Debug, Clone, Default)]
+pub struct MergedModuleDef {
+    pub def: ModuleDef,
+    /// Conflicts resolved according to the policy, empty for
+    /// [`ConflictPolicy::Error`]
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl ModuleDef {
+    /// Merge module definition fragments into one
+    ///
+    /// All fragments that name a DLL must name the same one. Header fields
+    /// such as `image_base` are taken from the first fragment that sets
+    /// them. Exports appearing identically in several fragments are kept
+    /// once, other duplicate names and ordinals are resolved by `policy`.
+    pub fn merge(
+        fragments: &[ModuleDef],
+        policy: ConflictPolicy,
+    ) -> Result<MergedModuleDef, Error> {
+        let mut merged = MergedModuleDef::default();
+        let mut import_name_from = 0;
+        for (index, fragment) in fragments.iter().enumerate() {
+            if fragment.import_name.is_empty() {
+                continue;
+            }
+            if merged.def.import_name.is_empty() {
+                merged.def.import_name = fragment.import_name.clone();
+                import_name_from = index;
+            } else if !merged
+                .def
+                .import_name
+                .eq_ignore_ascii_case(&fragment.import_name)
+            {
+                return Err(Error::validation(format!(
+                    "import name mismatch: fragment {} has {}, fragment {} has {}",
+                    import_name_from, merged.def.import_name, index, fragment.import_name
+                )));
+            }
+        }
+        let def = &mut merged.def;
+        for fragment in fragments {
+            let first_set = |field: &mut u64, value: u64| {
+                if *field == 0 {
+                    *field = value;
+                }
+            };
+            first_set(&mut def.image_base, fragment.image_base);
+            first_set(&mut def.stack_reserve, fragment.stack_reserve);
+            first_set(&mut def.stack_commit, fragment.stack_commit);
+            first_set(&mut def.heap_reserve, fragment.heap_reserve);
+            first_set(&mut def.heap_commit, fragment.heap_commit);
+            if (def.major_image_version, def.minor_image_version) == (0, 0) {
+                def.major_image_version = fragment.major_image_version;
+                def.minor_image_version = fragment.minor_image_version;
+            }
+            if (def.major_os_version, def.minor_os_version) == (0, 0) {
+                def.major_os_version = fragment.major_os_version;
+                def.minor_os_version = fragment.minor_os_version;
+            }
+        }
+
+        // Exports in merge order along with their fragment, `None` once
+        // replaced by a later fragment.
+        let mut exports: Vec<Option<(&ShortExport, usize)>> = Vec::new();
+        let mut by_name: HashMap<&str, usize> = HashMap::new();
+        let mut by_ordinal: HashMap<u16, usize> = HashMap::new();
+        for (index, fragment) in fragments.iter().enumerate() {
+            'exports: for export in &fragment.exports {
+                let name = export.exported_name();
+                if let Some(&slot) = by_name.get(name) {
+                    let (existing, _) = exports[slot].expect("live slot");
+                    if existing == export {
+                        continue;
+                    }
+                }
+                // Replacing an export by name can still leave its ordinal
+                // taken by another one, resolve until neither clashes.
+                loop {
+                    let clash = if let Some(&slot) = by_name.get(name) {
+                        Some((slot, ConflictKind::Name(name.to_string())))
+                    } else if export.ordinal != 0 {
+                        by_ordinal
+                            .get(&export.ordinal)
+                            .map(|&slot| (slot, ConflictKind::Ordinal(export.ordinal)))
+                    } else {
+                        None
+                    };
+                    let Some((slot, kind)) = clash else {
+                        break;
+                    };
+                    let (existing, existing_index) = exports[slot].expect("live slot");
+                    match policy {
+                        ConflictPolicy::Error => {
+                            let what = match &kind {
+                                ConflictKind::Name(name) => format!("export {}", name),
+                                ConflictKind::Ordinal(ordinal) => format!("ordinal {}", ordinal),
+                            };
+                            return Err(Error::validation(format!(
+                                "conflicting {} in fragment {} and fragment {}",
+                                what, existing_index, index
+                            )));
+                        }
+                        ConflictPolicy::FirstWins => {
+                            merged.conflicts.push(MergeConflict {
+                                kind,
+                                kept: existing_index,
+                                dropped: index,
+                            });
+                            continue 'exports;
+                        }
+                        ConflictPolicy::LastWins => {
+                            merged.conflicts.push(MergeConflict {
+                                kind,
+                                kept: index,
+                                dropped: existing_index,
+                            });
+                            exports[slot] = None;
+                            by_name.remove(existing.exported_name());
+                            if existing.ordinal != 0 {
+                                by_ordinal.remove(&existing.ordinal);
+                            }
+                        }
+                    }
+                }
+
+                by_name.insert(name, exports.len());
+                if export.ordinal != 0 {
+                    by_ordinal.insert(export.ordinal, exports.len());
+                }
+                exports.push(Some((export, index)));
+            }
+        }
+        merged.def.exports = exports
+            .into_iter()
+            .flatten()
+            .map(|(export, _)| export.clone())
+            .collect();
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MachineType;
+
+    fn parse(def: &str) -> ModuleDef {
+        ModuleDef::parse(def, MachineType::AMD64).unwrap()
+    }
+
+    fn names(def: &ModuleDef) -> Vec<&str> {
+        def.exports.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = ModuleDef::merge(
+            &[
+                parse("EXPORTS\nfoo @1\nshared\n"),
+                parse("LIBRARY sdk.dll BASE = 4096\nEXPORTS\nbar @2\nshared\n"),
+                parse("LIBRARY SDK.DLL\nVERSION 1.2\nEXPORTS\nbaz DATA\n"),
+            ],
+            ConflictPolicy::Error,
+        )
+        .unwrap();
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.def.import_name, "sdk.dll");
+        assert_eq!(merged.def.image_base, 4096);
+        assert_eq!(merged.def.major_image_version, 1);
+        assert_eq!(merged.def.minor_image_version, 2);
+        assert_eq!(names(&merged.def), ["foo", "shared", "bar", "baz"]);
+    }
+
+    #[test]
+    fn test_merge_import_name_mismatch() {
+        let err = ModuleDef::merge(
+            &[
+                parse("LIBRARY a.dll"),
+                parse("EXPORTS\nfoo"),
+                parse("LIBRARY b.dll"),
+            ],
+            ConflictPolicy::LastWins,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "import name mismatch: fragment 0 has a.dll, fragment 2 has b.dll"
+        );
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let fragments = [
+            parse("EXPORTS\nfoo @1\nbar @2\n"),
+            parse("EXPORTS\nfoo DATA\nbaz @2\n"),
+        ];
+
+        let err = ModuleDef::merge(&fragments, ConflictPolicy::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "conflicting export foo in fragment 0 and fragment 1"
+        );
+
+        let merged = ModuleDef::merge(&fragments, ConflictPolicy::FirstWins).unwrap();
+        assert_eq!(names(&merged.def), ["foo", "bar"]);
+        assert!(!merged.def.exports[0].data);
+        assert_eq!(
+            merged.conflicts,
+            [
+                MergeConflict {
+                    kind: ConflictKind::Name("foo".to_string()),
+                    kept: 0,
+                    dropped: 1,
+                },
+                MergeConflict {
+                    kind: ConflictKind::Ordinal(2),
+                    kept: 0,
+                    dropped: 1,
+                },
+            ]
+        );
+
+        let merged = ModuleDef::merge(&fragments, ConflictPolicy::LastWins).unwrap();
+        assert_eq!(names(&merged.def), ["foo", "baz"]);
+        assert!(merged.def.exports[0].data);
+        assert_eq!(merged.conflicts.len(), 2);
+        assert!(merged
+            .conflicts
+            .iter()
+            .all(|c| c.kept == 1 && c.dropped == 0));
+    }
+
+    #[test]
+    fn test_merge_last_wins_ordinal_clash() {
+        // Replacing foo by name moves it onto bar's ordinal.
+        let fragments = [
+            parse("EXPORTS\nfoo @1\nbar @2\n"),
+            parse("EXPORTS\nfoo @2\n"),
+        ];
+        let merged = ModuleDef::merge(&fragments, ConflictPolicy::LastWins).unwrap();
+        assert_eq!(names(&merged.def), ["foo"]);
+        assert_eq!(merged.def.exports[0].ordinal, 2);
+        assert_eq!(
+            merged.conflicts,
+            [
+                MergeConflict {
+                    kind: ConflictKind::Name("foo".to_string()),
+                    kept: 1,
+                    dropped: 0,
+                },
+                MergeConflict {
+                    kind: ConflictKind::Ordinal(2),
+                    kept: 1,
+                    dropped: 0,
+                },
+            ]
+        );
+
+        let merged = ModuleDef::merge(&fragments, ConflictPolicy::FirstWins).unwrap();
+        assert_eq!(names(&merged.def), ["foo", "bar"]);
+        assert_eq!(merged.conflicts.len(), 1);
+    }
+}
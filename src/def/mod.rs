@@ -1,9 +1,22 @@
-use std::io::Error;
+use std::fmt;
 
+pub use self::diff::{Change, ChangeKind, Compatibility, ExportKind, ModuleDiff};
+pub use self::export_all::ExportAllOptions;
+pub use self::merge::{ConflictKind, ConflictPolicy, MergeConflict, MergedModuleDef};
+pub(crate) use self::parser::is_decorated;
 use self::parser::Parser;
-use crate::MachineType;
+pub use self::transform::{Pattern, Transform, TransformReport};
+use crate::inspect::{demangle_arm64ec, ImportNameType};
+use crate::{Error, Flavor, Limits, MachineType};
 
+mod diff;
+mod directive;
+mod export_all;
+mod merge;
 mod parser;
+mod preprocess;
+mod spec;
+mod transform;
 
 /// Simple .DEF file parser
 #[derive(Debug, Clone, Default)]
@@ -22,13 +35,30 @@ pub struct ModuleDef {
 }
 
 impl ModuleDef {
+    /// Parse .def text without bounds on its size
+    ///
+    /// Use [`parse_with_limits`](Self::parse_with_limits) for untrusted
+    /// input.
     pub fn parse(def: &str, machine: MachineType) -> Result<ModuleDef, Error> {
         Parser::new(def, machine).parse()
     }
+
+    /// Parse untrusted .def text, failing as soon as it exceeds `limits`
+    ///
+    /// This is a separate entry point so that [`parse`](Self::parse) keeps
+    /// its signature for the trusted .def files most callers have.
+    pub fn parse_with_limits(
+        def: &str,
+        machine: MachineType,
+        limits: &Limits,
+    ) -> Result<ModuleDef, Error> {
+        limits.check_def_size(def.len())?;
+        Parser::new(def, machine).limits(*limits).parse()
+    }
 }
 
 /// COFF short export
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ShortExport {
     /// The name of the export as specified in the .def file or on the command
     /// line, i.e. "foo" in "/EXPORT:foo", and "bar" in "/EXPORT:foo=bar"
@@ -41,9 +71,156 @@ pub struct ShortExport {
     /// Creates a weak alias. This is the name of the weak aliasee. In a .def
     /// file, this is "baz" in "EXPORTS\nfoo = bar == baz".
     pub alias_target: String,
+    /// The name the import resolves to in the DLL, overriding the one
+    /// derived from `name`. Set by [`ModuleDef::transform`] when renaming.
+    pub export_as: Option<String>,
     pub ordinal: u16,
     pub no_name: bool,
     pub data: bool,
     pub private: bool,
     pub constant: bool,
+    /// Where the export is forwarded to, i.e. "other.bar" in
+    /// "foo = other.bar". `name` holds the forwarder string as written.
+    pub forward: Option<Forward>,
+}
+
+impl ShortExport {
+    /// Apply the i386 mangling of [`ModuleDef::parse`], prefixing names
+    /// that aren't decorated with `_`, does nothing for other machines
+    pub fn mangle(&mut self, machine: MachineType) {
+        if machine != MachineType::I386 {
+            return;
+        }
+        // A forwarder string isn't a symbol, so it isn't mangled.
+        if !is_decorated(&self.name) && self.forward.is_none() {
+            self.name.insert(0, '_');
+        }
+        if let Some(ext_name) = self.ext_name.as_mut() {
+            if !is_decorated(ext_name) {
+                ext_name.insert(0, '_');
+            }
+        }
+    }
+
+    /// The name the DLL exports this under
+    pub(crate) fn exported_name(&self) -> &str {
+        self.ext_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// The name an import of this export resolves to in the DLL, as picked
+    /// by the import library writer of `flavor`
+    pub(crate) fn import_name(&self, machine: MachineType, flavor: Flavor) -> String {
+        if let Some(export_as) = &self.export_as {
+            return export_as.clone();
+        }
+        match flavor {
+            Flavor::Msvc => self.msvc_import_name(machine),
+            // Remove i386 mangling added by the def parser, decorated names
+            // like "foo@4" have none.
+            Flavor::Gnu => match machine {
+                MachineType::I386 => {
+                    let name = self.exported_name();
+                    name.strip_prefix('_').unwrap_or(name).to_string()
+                }
+                _ => self.exported_name().to_string(),
+            },
+        }
+    }
+
+    /// The import name from the name type `ar_archive_writer` gives the
+    /// export's short import
+    fn msvc_import_name(&self, machine: MachineType) -> String {
+        // A weak alias resolves to its aliasee, whatever name type the
+        // writer expresses that with.
+        if !self.alias_target.is_empty() {
+            return self.alias_target.clone();
+        }
+        let symbol = if self.symbol_name.is_empty() {
+            &self.name
+        } else {
+            &self.symbol_name
+        };
+        let name = match &self.ext_name {
+            Some(ext_name) => replace_name(symbol, &self.name, ext_name),
+            None => symbol.clone(),
+        };
+        // Functions on ARM64EC are imported by their demangled name.
+        let arm64ec = matches!(machine, MachineType::ARM64EC | MachineType::ARM64X);
+        if arm64ec && !self.data && !self.constant {
+            return demangle_arm64ec(&name);
+        }
+        let name_type = if self.name.starts_with('_') && self.name.contains('@') {
+            ImportNameType::Name
+        } else if *symbol != self.name {
+            ImportNameType::Undecorate
+        } else if machine == MachineType::I386 && symbol.starts_with('_') {
+            ImportNameType::NoPrefix
+        } else {
+            ImportNameType::Name
+        };
+        name_type.apply(&name).unwrap_or(&name).to_string()
+    }
+}
+
+/// Substitute `to` for `from` in `symbol` like `ar_archive_writer` does for
+/// renamed exports, retrying without the i386 underscores
+fn replace_name(symbol: &str, from: &str, to: &str) -> String {
+    let stripped = from.strip_prefix('_').zip(to.strip_prefix('_'));
+    let found = symbol
+        .split_once(from)
+        .map(|split| (split, to))
+        .or_else(|| {
+            let (from, to) = stripped?;
+            Some((symbol.split_once(from)?, to))
+        });
+    match found {
+        Some(((before, after), to)) => format!("{}{}{}", before, to, after),
+        None => symbol.to_string(),
+    }
+}
+
+/// Target of a forwarded export
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forward {
+    /// DLL the export is forwarded to, without the `.dll` extension
+    pub dll: String,
+    pub target: ForwardTarget,
+}
+
+/// Export a forwarder resolves to in the target DLL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForwardTarget {
+    /// By name, "bar" in "other.bar"
+    Name(String),
+    /// By ordinal, 7 in "other.#7"
+    Ordinal(u16),
+}
+
+impl Forward {
+    /// Parse a forwarder string such as "other.bar" or "other.#7"
+    pub fn parse(forward: &str) -> Option<Self> {
+        // Like the Windows loader, split at the first dot.
+        let (dll, target) = forward.split_once('.')?;
+        if dll.is_empty() || target.is_empty() {
+            return None;
+        }
+        let target = match target.strip_prefix('#').map(str::parse) {
+            Some(Ok(ordinal)) => ForwardTarget::Ordinal(ordinal),
+            _ => ForwardTarget::Name(target.to_string()),
+        };
+        Some(Forward {
+            dll: dll.to_string(),
+            target,
+        })
+    }
+}
+
+/// Formats as the forwarder string stored in the export table
+impl fmt::Display for Forward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.target {
+            ForwardTarget::Name(name) => write!(f, "{}.{}", self.dll, name),
+            ForwardTarget::Ordinal(ordinal) => write!(f, "{}.#{}", self.dll, ordinal),
+        }
+    }
 }
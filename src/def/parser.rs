@@ -1,9 +1,8 @@
-use std::io::{Error, ErrorKind};
 use std::iter::Peekable;
 use std::str::CharIndices;
 
-use super::{ModuleDef, ShortExport};
-use crate::MachineType;
+use super::{Forward, ModuleDef, ShortExport};
+use crate::{Error, Limits, MachineType};
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -32,13 +31,10 @@ enum TokenKind {
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Token<'a> {
     kind: TokenKind,
-    value: Option<&'a str>,
-}
-
-impl<'a> Token<'a> {
-    fn unwrap_value(&self) -> &'a str {
-        self.value.expect("token value missing")
-    }
+    /// Token text, empty at the end of the input
+    value: &'a str,
+    /// Byte offset in the text, for error locations
+    offset: usize,
 }
 
 #[derive(Debug)]
@@ -56,38 +52,39 @@ impl<'a> Lexer<'a> {
     }
 }
 
-impl<'a> Iterator for Lexer<'a> {
-    type Item = Token<'a>;
+fn is_space(c: char) -> bool {
+    matches!(c, '\r' | '\n' | ' ' | '\t' | '\x0B')
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some((i, c)) = self.chars.next() {
+impl<'a> Lexer<'a> {
+    /// Read the next token, the end of the input is an `Eof` token
+    fn next_token(&mut self) -> Token<'a> {
+        let token = |kind, value, offset| Token {
+            kind,
+            value,
+            offset,
+        };
+        loop {
+            let Some((i, c)) = self.chars.next() else {
+                return token(TokenKind::Eof, "", self.text.len());
+            };
             match c {
-                '\0' => Some(Token {
-                    kind: TokenKind::Eof,
-                    value: None,
-                }),
+                '\0' => return token(TokenKind::Eof, "", i),
                 ';' => {
                     for (_, next_c) in self.chars.by_ref() {
                         if next_c == '\n' {
                             break;
                         }
                     }
-                    self.next()
                 }
-                '=' => match self.chars.next_if(|&x| x.1 == '=').map(|x| x.1) {
-                    Some(_) => Some(Token {
-                        kind: TokenKind::EqualEqual,
-                        value: Some("=="),
-                    }),
-                    None => Some(Token {
-                        kind: TokenKind::Equal,
-                        value: Some("="),
-                    }),
-                },
-                ',' => Some(Token {
-                    kind: TokenKind::Comma,
-                    value: Some(","),
-                }),
+                c if is_space(c) => {}
+                '=' => {
+                    return match self.chars.next_if(|&x| x.1 == '=') {
+                        Some(_) => token(TokenKind::EqualEqual, "==", i),
+                        None => token(TokenKind::Equal, "=", i),
+                    }
+                }
+                ',' => return token(TokenKind::Comma, ",", i),
                 '"' => {
                     let mut end = i + 1;
                     for (j, next_c) in self.chars.by_ref() {
@@ -96,64 +93,61 @@ impl<'a> Iterator for Lexer<'a> {
                             break;
                         }
                     }
-                    Some(Token {
-                        kind: TokenKind::Identifier,
-                        value: Some(self.text[i + 1..end].trim()),
-                    })
+                    return token(TokenKind::Identifier, self.text[i + 1..end].trim(), i);
                 }
                 _ => {
-                    let mut end = i;
-                    for (j, next_c) in self.chars.by_ref() {
-                        match next_c {
-                            '=' | ',' | ';' | '\r' | '\n' | ' ' | '\t' | '\x0B' => {
-                                end = j;
-                                break;
-                            }
-                            _ => {
-                                end = j + next_c.len_utf8();
-                            }
+                    // Stop before the delimiter, `=`, `,` and `;` are tokens
+                    // of their own.
+                    let mut end = i + c.len_utf8();
+                    while let Some(&(j, next_c)) = self.chars.peek() {
+                        if is_space(next_c) || matches!(next_c, '=' | ',' | ';') {
+                            break;
                         }
+                        end = j + next_c.len_utf8();
+                        self.chars.next();
                     }
-                    let word = self.text[i..end].trim();
+                    let word = self.text[i..end].trim_start();
+                    let offset = end - word.len();
+                    let word = word.trim_end();
                     if word.is_empty() {
-                        self.next()
-                    } else {
-                        let kind = match word {
-                            "BASE" => TokenKind::KwBase,
-                            "CONSTANT" => TokenKind::KwConstant,
-                            "DATA" => TokenKind::KwData,
-                            "EXPORTS" => TokenKind::KwExports,
-                            "HEAPSIZE" => TokenKind::KwHeapsize,
-                            "LIBRARY" => TokenKind::KwLibrary,
-                            "NAME" => TokenKind::KwName,
-                            "NONAME" => TokenKind::KwNoname,
-                            "PRIVATE" => TokenKind::KwPrivate,
-                            "STACKSIZE" => TokenKind::KwStacksize,
-                            "VERSION" => TokenKind::KwVersion,
-                            _ => TokenKind::Identifier,
-                        };
-                        Some(Token {
-                            kind,
-                            value: Some(word),
-                        })
+                        continue;
                     }
+                    let kind = match word {
+                        "BASE" => TokenKind::KwBase,
+                        "CONSTANT" => TokenKind::KwConstant,
+                        "DATA" => TokenKind::KwData,
+                        "EXPORTS" => TokenKind::KwExports,
+                        "HEAPSIZE" => TokenKind::KwHeapsize,
+                        "LIBRARY" => TokenKind::KwLibrary,
+                        "NAME" => TokenKind::KwName,
+                        "NONAME" => TokenKind::KwNoname,
+                        "PRIVATE" => TokenKind::KwPrivate,
+                        "STACKSIZE" => TokenKind::KwStacksize,
+                        "VERSION" => TokenKind::KwVersion,
+                        _ => TokenKind::Identifier,
+                    };
+                    return token(kind, word, offset);
                 }
             }
-        } else {
-            Some(Token {
-                kind: TokenKind::Eof,
-                value: None,
-            })
         }
     }
 }
 
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_token())
+    }
+}
+
 #[derive(Debug)]
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     stack: Vec<Token<'a>>,
     def: ModuleDef,
     machine: MachineType,
+    limits: Limits,
 }
 
 impl<'a> Parser<'a> {
@@ -163,9 +157,15 @@ impl<'a> Parser<'a> {
             stack: Vec::new(),
             def: ModuleDef::default(),
             machine,
+            limits: Limits::default(),
         }
     }
 
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn parse(mut self) -> Result<ModuleDef> {
         loop {
             let eof = self.parse_one()?;
@@ -200,6 +200,7 @@ impl<'a> Parser<'a> {
             }
             TokenKind::KwLibrary | TokenKind::KwName => {
                 let (name, image_base) = self.parse_name()?;
+                self.limits.check_name(&name)?;
                 self.def.import_name = name;
                 self.def.image_base = image_base;
             }
@@ -208,19 +209,15 @@ impl<'a> Parser<'a> {
                 self.def.major_image_version = major;
                 self.def.minor_image_version = minor;
             }
-            _ => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("unknown directive: {}", token.unwrap_value()),
-                ))
-            }
+            _ => return Err(self.error(&token, format!("unknown directive: {}", token.value))),
         }
         Ok(false)
     }
 
     fn parse_export(&mut self, token: Token<'a>) -> Result<()> {
+        self.check_export_name(&token)?;
         let mut export = ShortExport {
-            name: token.unwrap_value().to_string(),
+            name: token.value.to_string(),
             ..Default::default()
         };
 
@@ -228,61 +225,52 @@ impl<'a> Parser<'a> {
         if token.kind == TokenKind::Equal {
             let token = self.read();
             if token.kind != TokenKind::Identifier {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
+                return Err(self.error(
+                    &token,
                     format!("expected identifier, found: {:?}", token.kind),
                 ));
             }
+            self.check_export_name(&token)?;
             export.ext_name = Some(export.name);
-            export.name = token.unwrap_value().to_string();
+            export.name = token.value.to_string();
+            // "foo = other.bar" forwards foo to bar in other.dll
+            export.forward = Forward::parse(&export.name);
         } else {
             self.stack.push(token);
         }
 
-        if self.machine == MachineType::I386 {
-            if !is_decorated(&export.name) {
-                export.name = format!("_{}", export.name);
-            }
-            if let Some(ext_name) = export.ext_name.as_ref() {
-                if !is_decorated(ext_name) {
-                    export.ext_name = Some(format!("_{}", ext_name));
-                }
-            }
-        }
+        export.mangle(self.machine);
 
         loop {
             let token = self.read();
-            if token.kind == TokenKind::Identifier
-                && token.value.map(|v| v.starts_with('@')).unwrap_or_default()
-            {
-                let value = token.unwrap_value();
+            if token.kind == TokenKind::Identifier && token.value.starts_with('@') {
+                let value = token.value;
                 if value == "@" {
                     // "foo @ 10"
                     let token = self.read();
-                    if let Some(value) = token.value {
-                        match value.parse() {
-                            Ok(ordinal) => export.ordinal = ordinal,
-                            Err(_) => {
-                                return Err(Error::new(
-                                    ErrorKind::InvalidInput,
-                                    format!("invalid ordinal: {}", value),
-                                ))
-                            }
-                        }
-                    } else {
-                        return Err(Error::new(
-                            ErrorKind::InvalidInput,
+                    if token.kind == TokenKind::Eof {
+                        return Err(self.error(
+                            &token,
                             format!("expected identifier, found: {:?}", token.kind),
                         ));
                     }
-                } else if value[1..].parse::<u16>().is_err() {
+                    match token.value.parse() {
+                        Ok(ordinal) => export.ordinal = ordinal,
+                        Err(_) => {
+                            return Err(
+                                self.error(&token, format!("invalid ordinal: {}", token.value))
+                            )
+                        }
+                    }
+                } else if let Ok(ordinal) = value[1..].parse::<u16>() {
+                    // "foo @10"
+                    export.ordinal = ordinal;
+                } else {
                     // "foo \n @bar" - Not an ordinal modifier at all, but the next
                     // export (fastcall decorated) - complete the current one.
                     self.stack.push(token);
-                    self.def.exports.push(export);
-                    return Ok(());
+                    return self.push_export(export);
                 }
-                // "foo @10"
                 let token = self.read();
                 if token.kind == TokenKind::KwNoname {
                     export.no_name = true;
@@ -303,25 +291,22 @@ impl<'a> Parser<'a> {
                     export.private = true;
                 }
                 TokenKind::EqualEqual => {
-                    let token = self.read();
-                    if let Some(value) = token.value {
-                        export.alias_target = value.to_string();
-                    } else {
-                        return Err(Error::new(
-                            ErrorKind::InvalidInput,
-                            format!("expected identifier, found: {:?}", token.kind),
-                        ));
-                    }
+                    export.alias_target = self.read_name()?.to_string();
                     // Skipped mingw i386 handling
                     // See https://github.com/llvm/llvm-project/blob/09c2b7c35af8c4bad39f03e9f60df8bd07323028/llvm/lib/Object/COFFModuleDefinition.cpp#L282-L283
                 }
                 _ => {
                     self.stack.push(token);
-                    self.def.exports.push(export);
-                    break;
+                    return self.push_export(export);
                 }
             }
         }
+    }
+
+    fn push_export(&mut self, export: ShortExport) -> Result<()> {
+        self.limits.check_exports(self.def.exports.len() + 1)?;
+        self.limits.check_export(&export)?;
+        self.def.exports.push(export);
         Ok(())
     }
 
@@ -342,7 +327,7 @@ impl<'a> Parser<'a> {
         let mut name = String::new();
         let token = self.read();
         if token.kind == TokenKind::Identifier {
-            name = token.unwrap_value().to_string();
+            name = token.value.to_string();
         } else {
             self.stack.push(token);
             return Ok((name, 0));
@@ -351,10 +336,7 @@ impl<'a> Parser<'a> {
         if token.kind == TokenKind::KwBase {
             let token = self.read();
             if token.kind != TokenKind::Equal {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("expected equal, found: {:?}", token.kind),
-                ));
+                return Err(self.error(&token, format!("expected equal, found: {:?}", token.kind)));
             }
             let base = self.read_as_int()?;
             Ok((name, base))
@@ -368,55 +350,96 @@ impl<'a> Parser<'a> {
     fn parse_version(&mut self) -> Result<(u32, u32)> {
         let token = self.read();
         if token.kind != TokenKind::Identifier {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(self.error(
+                &token,
                 format!("expected identifier, found: {:?}", token.kind),
             ));
         }
-        let value = token.unwrap_value();
+        let value = token.value;
         match value.split_once('.') {
             Some((major, minor)) => {
                 let major = major
                     .parse::<u32>()
-                    .map_err(|_| Error::new(ErrorKind::InvalidInput, "expected integer"))?;
+                    .map_err(|_| self.error(&token, "expected integer"))?;
                 let minor = minor
                     .parse::<u32>()
-                    .map_err(|_| Error::new(ErrorKind::InvalidInput, "expected integer"))?;
+                    .map_err(|_| self.error(&token, "expected integer"))?;
                 Ok((major, minor))
             }
             None => {
                 let major = value
                     .parse::<u32>()
-                    .map_err(|_| Error::new(ErrorKind::InvalidInput, "expected integer"))?;
+                    .map_err(|_| self.error(&token, "expected integer"))?;
                 Ok((major, 0))
             }
         }
     }
 
+    fn error(&self, token: &Token, message: impl Into<String>) -> Error {
+        let before = &self.lexer.text[..token.offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count()
+            + 1;
+        Error::parse(line, column, message)
+    }
+
     fn read(&mut self) -> Token<'a> {
         if let Some(token) = self.stack.pop() {
             token
         } else {
-            self.lexer.next().expect("unexpected EOF")
+            self.lexer.next_token()
+        }
+    }
+
+    /// A lone `@` starts an ordinal, it can't be a name, nor can `""`
+    fn check_export_name(&self, token: &Token) -> Result<()> {
+        if token.value.is_empty() || token.value == "@" {
+            return Err(self.error(
+                token,
+                format!("expected export name, found: {:?}", token.value),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Read a name, keywords included
+    fn read_name(&mut self) -> Result<&'a str> {
+        let token = self.read();
+        match token.kind {
+            TokenKind::Eof | TokenKind::Equal | TokenKind::EqualEqual | TokenKind::Comma => {
+                Err(self.error(
+                    &token,
+                    format!("expected identifier, found: {:?}", token.kind),
+                ))
+            }
+            _ if token.value.is_empty() => {
+                Err(self.error(&token, "expected identifier, found: \"\""))
+            }
+            _ => Ok(token.value),
         }
     }
 
     fn read_as_int(&mut self) -> Result<u64> {
         let token = self.read();
         if token.kind != TokenKind::Identifier {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(self.error(
+                &token,
                 format!("expected identifier, found: {:?}", token.kind),
             ));
         }
         token
-            .unwrap_value()
+            .value
             .parse()
-            .map_err(|_| Error::new(ErrorKind::InvalidInput, "expected integer"))
+            .map_err(|_| self.error(&token, "expected integer"))
     }
 }
 
-fn is_decorated(sym: &str) -> bool {
+pub(crate) fn is_decorated(sym: &str) -> bool {
     sym.starts_with('@') || sym.starts_with('?') || sym.contains('@')
 }
 
@@ -431,21 +454,24 @@ mod test {
             lexer.next(),
             Some(Token {
                 kind: TokenKind::KwName,
-                value: Some("NAME"),
+                value: "NAME",
+                offset: 0,
             })
         );
         assert_eq!(
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Identifier,
-                value: Some("foo.dll"),
+                value: "foo.dll",
+                offset: 5,
             })
         );
         assert_eq!(
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Eof,
-                value: None,
+                value: "",
+                offset: 12,
             })
         );
 
@@ -454,7 +480,8 @@ mod test {
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Eof,
-                value: None,
+                value: "",
+                offset: 0,
             })
         );
 
@@ -463,7 +490,8 @@ mod test {
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Eof,
-                value: None,
+                value: "",
+                offset: 0,
             })
         );
 
@@ -472,42 +500,48 @@ mod test {
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Equal,
-                value: Some("="),
+                value: "=",
+                offset: 0,
             })
         );
         assert_eq!(
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Comma,
-                value: Some(","),
+                value: ",",
+                offset: 1,
             })
         );
         assert_eq!(
             lexer.next(),
             Some(Token {
                 kind: TokenKind::EqualEqual,
-                value: Some("=="),
+                value: "==",
+                offset: 2,
             })
         );
         assert_eq!(
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Identifier,
-                value: Some("CODE"),
+                value: "CODE",
+                offset: 4,
             })
         );
         assert_eq!(
             lexer.next(),
             Some(Token {
                 kind: TokenKind::KwBase,
-                value: Some("BASE"),
+                value: "BASE",
+                offset: 10,
             })
         );
         assert_eq!(
             lexer.next(),
             Some(Token {
                 kind: TokenKind::Eof,
-                value: None,
+                value: "",
+                offset: 15,
             })
         );
     }
@@ -557,6 +591,101 @@ PyBytesIter_Type DATA"#,
         assert!(def.exports[3].data);
     }
 
+    #[test]
+    fn test_parser_ordinals() {
+        let def = Parser::new(
+            "EXPORTS\nfoo @1\nbar @ 2 NONAME\nbaz @3 NONAME DATA\n@qux@8\nquux @10\n@corge@4 @65535",
+            MachineType::AMD64,
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(def.exports.len(), 6);
+        assert_eq!(def.exports[0].ordinal, 1);
+        assert!(!def.exports[0].no_name);
+        assert_eq!(def.exports[1].ordinal, 2);
+        assert!(def.exports[1].no_name);
+        assert_eq!(def.exports[2].ordinal, 3);
+        assert!(def.exports[2].no_name);
+        assert!(def.exports[2].data);
+        assert_eq!(def.exports[3].name, "@qux@8");
+        assert_eq!(def.exports[3].ordinal, 0);
+        assert_eq!(def.exports[4].name, "quux");
+        assert_eq!(def.exports[4].ordinal, 10);
+        assert_eq!(def.exports[5].name, "@corge@4");
+        assert_eq!(def.exports[5].ordinal, 65535);
+    }
+
+    #[test]
+    fn test_parser_forwards() {
+        use crate::def::ForwardTarget;
+
+        let def = Parser::new(
+            "EXPORTS\nHeapAlloc = NTDLL.RtlAllocateHeap\nfoo = other.#7 @3\nbar = baz\n",
+            MachineType::I386,
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(def.exports.len(), 3);
+        let forward = def.exports[0].forward.as_ref().unwrap();
+        assert_eq!(forward.dll, "NTDLL");
+        assert_eq!(
+            forward.target,
+            ForwardTarget::Name("RtlAllocateHeap".to_string())
+        );
+        assert_eq!(forward.to_string(), "NTDLL.RtlAllocateHeap");
+        assert_eq!(def.exports[0].name, "NTDLL.RtlAllocateHeap");
+        assert_eq!(def.exports[0].ext_name.as_deref(), Some("_HeapAlloc"));
+        let forward = def.exports[1].forward.as_ref().unwrap();
+        assert_eq!(forward.target, ForwardTarget::Ordinal(7));
+        assert_eq!(forward.to_string(), "other.#7");
+        assert_eq!(def.exports[1].ordinal, 3);
+        assert_eq!(def.exports[2].forward, None);
+        assert_eq!(def.exports[2].name, "_baz");
+    }
+
+    #[test]
+    fn test_lexer_delimiters() {
+        let tokens: Vec<_> = Lexer::new("foo=bar,baz;comment\nx")
+            .take_while(|token| token.kind != TokenKind::Eof)
+            .map(|token| token.value)
+            .collect();
+        assert_eq!(tokens, ["foo", "=", "bar", ",", "baz", "x"]);
+
+        let def = Parser::new("EXPORTS\nfoo=bar\ny", MachineType::AMD64)
+            .parse()
+            .unwrap();
+        assert_eq!(def.exports[0].name, "bar");
+        assert_eq!(def.exports[0].ext_name.as_deref(), Some("foo"));
+        assert_eq!(def.exports[1].name, "y");
+    }
+
+    #[test]
+    fn test_parser_error_location() {
+        for (text, line, column, message) in [
+            ("FOO", 1, 1, "unknown directive: FOO"),
+            ("EXPORTS\n  foo @ bar\n", 2, 9, "invalid ordinal: bar"),
+            ("LIBRARY foo.dll\nVERSION 1.x", 2, 9, "expected integer"),
+            ("EXPORTS\nfoo ==", 2, 7, "expected identifier, found: Eof"),
+            (
+                "EXPORTS\nfoo = @",
+                2,
+                7,
+                "expected export name, found: \"@\"",
+            ),
+            ("EXPORTS\n\"\"", 2, 1, "expected export name, found: \"\""),
+            ("HEAPSIZE ; x\n\u{e9}", 2, 1, "expected integer"),
+        ] {
+            match Parser::new(text, MachineType::AMD64).parse().unwrap_err() {
+                Error::Parse {
+                    line: l,
+                    column: c,
+                    message: m,
+                } => assert_eq!((l, c, m.as_str()), (line, column, message), "{}", text),
+                err => panic!("{}: {:?}", text, err),
+            }
+        }
+    }
+
     #[test]
     fn test_parser_with_bad_input() {
         Parser::new(" \u{b}EXPORTS D \u{b}===", MachineType::AMD64)
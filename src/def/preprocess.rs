@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+
+use super::ModuleDef;
+use crate::{Error, MachineType};
+
+impl ModuleDef {
+    /// Run a `.def` template through a C preprocessor subset, like
+    /// `cl /EP` does for `.def.in` files
+    ///
+    /// Supports `#if`, `#ifdef`, `#ifndef`, `#elif`, `#else`, `#endif`,
+    /// object-like `#define` and `#undef`, and `#error`. `#if` expressions
+    /// take integers, `defined`, macros, and C's arithmetic, comparison and
+    /// logical operators; undefined identifiers are 0. Macros are substituted
+    /// outside of string literals in the lines kept. Directives and skipped
+    /// lines become empty lines so line numbers stay the same.
+    pub fn preprocess(template: &str, defines: &HashMap<String, String>) -> Result<String, Error> {
+        Preprocessor {
+            defines: defines.clone(),
+            stack: Vec::new(),
+        }
+        .run(template)
+    }
+
+    /// Preprocess a `.def` template for `machine` and parse the result
+    ///
+    /// The macros `cl` predefines for the architecture, like `_M_IX86` or
+    /// `_WIN64`, are defined in addition to `defines`, which take precedence.
+    pub fn parse_template(
+        template: &str,
+        machine: MachineType,
+        defines: &HashMap<String, String>,
+    ) -> Result<ModuleDef, Error> {
+        let mut all = machine_defines(machine);
+        all.extend(defines.iter().map(|(k, v)| (k.clone(), v.clone())));
+        ModuleDef::parse(&ModuleDef::preprocess(template, &all)?, machine)
+    }
+}
+
+fn machine_defines(machine: MachineType) -> HashMap<String, String> {
+    let defines: &[(&str, &str)] = match machine {
+        MachineType::I386 => &[("_M_IX86", "600")],
+        MachineType::ARMNT => &[("_M_ARM", "7")],
+        MachineType::AMD64 => &[("_M_AMD64", "100"), ("_M_X64", "100"), ("_WIN64", "1")],
+        MachineType::ARM64 | MachineType::ARM64X => &[("_M_ARM64", "1"), ("_WIN64", "1")],
+        MachineType::ARM64EC => &[
+            ("_M_ARM64EC", "1"),
+            ("_M_AMD64", "100"),
+            ("_M_X64", "100"),
+            ("_WIN64", "1"),
+        ],
+    };
+    defines
+        .iter()
+        .chain(&[("_WIN32", "1")])
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// State of an `#if` group
+struct Conditional {
+    /// Whether the enclosing group is active
+    parent_active: bool,
+    /// Whether the current branch is active
+    active: bool,
+    /// Whether an earlier branch was taken
+    taken: bool,
+    seen_else: bool,
+    /// Line and column of the `#if`
+    location: (usize, usize),
+}
+
+struct Preprocessor {
+    defines: HashMap<String, String>,
+    stack: Vec<Conditional>,
+}
+
+impl Preprocessor {
+    fn active(&self) -> bool {
+        self.stack.last().is_none_or(|cond| cond.active)
+    }
+
+    fn run(mut self, template: &str) -> Result<String, Error> {
+        let mut output = String::with_capacity(template.len());
+        for (index, line) in template.lines().enumerate() {
+            let column = line.chars().take_while(|c| c.is_whitespace()).count() + 1;
+            let location = (index + 1, column);
+            match line.trim_start().strip_prefix('#') {
+                Some(directive) => self
+                    .directive(directive.trim(), location)
+                    .map_err(|msg| Error::parse(location.0, location.1, msg))?,
+                None if self.active() => output.push_str(&self.expand(line, &mut Vec::new())),
+                None => {}
+            }
+            output.push('\n');
+        }
+        if let Some(cond) = self.stack.last() {
+            let (line, column) = cond.location;
+            return Err(Error::parse(line, column, "unterminated #if"));
+        }
+        Ok(output)
+    }
+
+    fn directive(&mut self, directive: &str, location: (usize, usize)) -> Result<(), String> {
+        let len = directive
+            .find(|c: char| !is_ident_char(c))
+            .unwrap_or(directive.len());
+        let (name, rest) = (&directive[..len], directive[len..].trim());
+        match name {
+            "if" | "ifdef" | "ifndef" => {
+                let parent_active = self.active();
+                let active = parent_active
+                    && match name {
+                        "if" => self.eval(rest)? != 0,
+                        "ifdef" => self.defines.contains_key(identifier(rest)?),
+                        _ => !self.defines.contains_key(identifier(rest)?),
+                    };
+                self.stack.push(Conditional {
+                    parent_active,
+                    active,
+                    taken: active,
+                    seen_else: false,
+                    location,
+                });
+            }
+            "elif" => {
+                let cond = self.stack.last().ok_or("#elif without #if")?;
+                if cond.seen_else {
+                    return Err("#elif after #else".to_string());
+                }
+                let active = cond.parent_active && !cond.taken && self.eval(rest)? != 0;
+                let cond = self.stack.last_mut().ok_or("#elif without #if")?;
+                cond.active = active;
+                cond.taken |= active;
+            }
+            "else" => {
+                let cond = self.stack.last_mut().ok_or("#else without #if")?;
+                if cond.seen_else {
+                    return Err("#else after #else".to_string());
+                }
+                cond.seen_else = true;
+                cond.active = cond.parent_active && !cond.taken;
+                cond.taken = true;
+            }
+            "endif" => {
+                self.stack.pop().ok_or("#endif without #if")?;
+            }
+            _ if !self.active() => {}
+            // The null directive
+            "" => {}
+            "define" => {
+                let len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+                let (name, value) = rest.split_at(len);
+                if value.starts_with('(') {
+                    return Err(format!("function-like macro not supported: {}", name));
+                }
+                self.defines
+                    .insert(identifier(name)?.to_string(), value.trim().to_string());
+            }
+            "undef" => {
+                self.defines.remove(identifier(rest)?);
+            }
+            "error" => return Err(format!("#error {}", rest)),
+            _ => return Err(format!("unsupported directive: #{}", name)),
+        }
+        Ok(())
+    }
+
+    /// Substitute macros in a line, those in `expanding` are left alone
+    fn expand<'a>(&'a self, line: &str, expanding: &mut Vec<&'a str>) -> String {
+        let mut output = String::with_capacity(line.len());
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c == '"' {
+                output.push(c);
+                for (_, c) in chars.by_ref() {
+                    output.push(c);
+                    if c == '"' {
+                        break;
+                    }
+                }
+            } else if is_ident_char(c) {
+                let mut end = start + c.len_utf8();
+                while let Some(&(index, c)) = chars.peek() {
+                    if !is_ident_char(c) {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    chars.next();
+                }
+                let ident = &line[start..end];
+                match self.defines.get_key_value(ident) {
+                    Some((name, value)) if !expanding.contains(&name.as_str()) => {
+                        expanding.push(name);
+                        output.push_str(&self.expand(value, expanding));
+                        expanding.pop();
+                    }
+                    _ => output.push_str(ident),
+                }
+            } else {
+                output.push(c);
+            }
+        }
+        output
+    }
+
+    fn eval(&self, expr: &str) -> Result<i64, String> {
+        let tokens = self.tokenize(expr, &mut Vec::new())?;
+        let mut parser = ExprParser { tokens, pos: 0 };
+        let value = parser.parse(0)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(value),
+            Some(Token::Num(num)) => Err(format!("unexpected {} in #if", num)),
+            Some(Token::Op(op)) => Err(format!("unexpected {} in #if", op)),
+        }
+    }
+
+    /// Lex an `#if` expression, resolving `defined` and macros
+    fn tokenize<'a>(
+        &'a self,
+        expr: &str,
+        expanding: &mut Vec<&'a str>,
+    ) -> Result<Vec<Token>, String> {
+        let mut tokens = Vec::new();
+        let mut rest = expr.trim_start();
+        while let Some(c) = rest.chars().next() {
+            if c.is_ascii_digit() {
+                let len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+                tokens.push(Token::Num(parse_number(&rest[..len])?));
+                rest = &rest[len..];
+            } else if is_ident_char(c) {
+                let len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+                let ident = &rest[..len];
+                rest = &rest[len..];
+                if ident == "defined" {
+                    let operand = rest.trim_start();
+                    let (name, after) = match operand.strip_prefix('(') {
+                        Some(inner) => {
+                            let (name, after) =
+                                inner.split_once(')').ok_or("expected ) after defined")?;
+                            (name.trim(), after)
+                        }
+                        None => {
+                            let len = operand
+                                .find(|c: char| !is_ident_char(c))
+                                .unwrap_or(operand.len());
+                            (&operand[..len], &operand[len..])
+                        }
+                    };
+                    let name = identifier(name)?;
+                    tokens.push(Token::Num(self.defines.contains_key(name) as i64));
+                    rest = after;
+                } else if let Some((name, value)) = self
+                    .defines
+                    .get_key_value(ident)
+                    .filter(|(name, _)| !expanding.contains(&name.as_str()))
+                {
+                    expanding.push(name);
+                    tokens.extend(self.tokenize(value, expanding)?);
+                    expanding.pop();
+                } else {
+                    tokens.push(Token::Num(0));
+                }
+            } else {
+                let op = ["&&", "||", "==", "!=", "<=", ">=", "<<", ">>"]
+                    .into_iter()
+                    .find(|op| rest.starts_with(op))
+                    .unwrap_or(&rest[..c.len_utf8()]);
+                if op.len() == 1 && !"()!~*/%+-<>&^|".contains(c) || !c.is_ascii() {
+                    return Err(format!("unexpected character in #if: {}", op));
+                }
+                tokens.push(Token::Op(op.to_string()));
+                rest = &rest[op.len()..];
+            }
+            rest = rest.trim_start();
+        }
+        Ok(tokens)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Num(i64),
+    Op(String),
+}
+
+/// Precedence climbing over C's binary operators
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn binary_op(&self) -> Option<(&str, u8)> {
+        let Some(Token::Op(op)) = self.tokens.get(self.pos) else {
+            return None;
+        };
+        let prec = match op.as_str() {
+            "||" => 1,
+            "&&" => 2,
+            "|" => 3,
+            "^" => 4,
+            "&" => 5,
+            "==" | "!=" => 6,
+            "<" | ">" | "<=" | ">=" => 7,
+            "<<" | ">>" => 8,
+            "+" | "-" => 9,
+            "*" | "/" | "%" => 10,
+            _ => return None,
+        };
+        Some((op, prec))
+    }
+
+    fn parse(&mut self, min_prec: u8) -> Result<i64, String> {
+        let mut lhs = self.unary()?;
+        while let Some((op, prec)) = self.binary_op() {
+            if prec <= min_prec {
+                break;
+            }
+            let op = op.to_string();
+            self.pos += 1;
+            let rhs = self.parse(prec)?;
+            lhs = match op.as_str() {
+                "||" => (lhs != 0 || rhs != 0) as i64,
+                "&&" => (lhs != 0 && rhs != 0) as i64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => (lhs == rhs) as i64,
+                "!=" => (lhs != rhs) as i64,
+                "<" => (lhs < rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                ">=" => (lhs >= rhs) as i64,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err("division by zero in #if".to_string()),
+                "/" => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Num(value)) => Ok(value),
+            Some(Token::Op(op)) => match op.as_str() {
+                "!" => Ok((self.unary()? == 0) as i64),
+                "~" => Ok(!self.unary()?),
+                "-" => Ok(self.unary()?.wrapping_neg()),
+                "+" => self.unary(),
+                "(" => {
+                    let value = self.parse(0)?;
+                    if self.tokens.get(self.pos) != Some(&Token::Op(")".to_string())) {
+                        return Err("expected ) in #if".to_string());
+                    }
+                    self.pos += 1;
+                    Ok(value)
+                }
+                _ => Err(format!("unexpected {} in #if", op)),
+            },
+            None => Err("expected expression in #if".to_string()),
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn identifier(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name.chars().all(is_ident_char)
+    {
+        return Err(format!("expected identifier, found: {:?}", name));
+    }
+    Ok(name)
+}
+
+/// Parse a C integer literal, ignoring `u` and `l` suffixes
+fn parse_number(literal: &str) -> Result<i64, String> {
+    let digits = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    value.map_err(|_| format!("invalid number in #if: {}", literal))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(expr: &str) -> Result<i64, String> {
+        let defines = [
+            ("ONE", "1"),
+            ("TWO", "ONE + ONE"),
+            ("EMPTY", ""),
+            ("SELF", "SELF"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        Preprocessor {
+            defines,
+            stack: Vec::new(),
+        }
+        .eval(expr)
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        // Substitution is textual, as in C
+        assert_eq!(eval("TWO * 3"), Ok(4));
+        assert_eq!(eval("defined(ONE) && !defined UNKNOWN"), Ok(1));
+        assert_eq!(eval("UNKNOWN || 0x10 == 16"), Ok(1));
+        assert_eq!(eval("010 >= 8 && -1 < 0 && ~0 == -1"), Ok(1));
+        assert_eq!(eval("7 % 4 | 1 << 3 ^ 1"), Ok(11));
+        assert_eq!(eval("0600L"), Ok(384));
+        assert_eq!(eval("1 / 0").unwrap_err(), "division by zero in #if");
+        assert_eq!(eval("EMPTY").unwrap_err(), "expected expression in #if");
+        assert_eq!(eval("(1").unwrap_err(), "expected ) in #if");
+        assert_eq!(eval("1 2").unwrap_err(), "unexpected 2 in #if");
+        assert_eq!(eval("SELF"), Ok(0));
+        assert_eq!(
+            eval("1 ? 2 : 3").unwrap_err(),
+            "unexpected character in #if: ?"
+        );
+        assert_eq!(eval("0x").unwrap_err(), "invalid number in #if: 0x");
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut defines = HashMap::new();
+        defines.insert("A".to_string(), "B".to_string());
+        defines.insert("B".to_string(), "A".to_string());
+        defines.insert("NAME".to_string(), "python39".to_string());
+        let pp = Preprocessor {
+            defines,
+            stack: Vec::new(),
+        };
+        assert_eq!(
+            pp.expand("LIBRARY \"NAME.dll\" ; NAME_x NAME", &mut Vec::new()),
+            "LIBRARY \"NAME.dll\" ; NAME_x python39"
+        );
+        assert_eq!(pp.expand("foo@4 1NAME", &mut Vec::new()), "foo@4 1NAME");
+        // Like cpp, a macro isn't expanded within itself.
+        assert_eq!(pp.expand("A B", &mut Vec::new()), "A B");
+    }
+}
@@ -0,0 +1,272 @@
+use super::{Forward, ModuleDef, ShortExport};
+use crate::{Error, MachineType};
+
+/// Entry point types of a `.spec` file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryType {
+    Stdcall,
+    Cdecl,
+    Varargs,
+    Thiscall,
+    Fastcall,
+    Extern,
+    Stub,
+    Equate,
+}
+
+impl ModuleDef {
+    /// Parse a Wine `.spec` file,
+    /// `ordinal type [flags] name[(args)] [handler]` per line
+    ///
+    /// Produces the same exports as parsing the `.def` file winebuild
+    /// generates from it: i386 `stdcall` and `fastcall` names get their `@N`
+    /// decoration from the argument list, `extern` entries are `DATA` and a
+    /// `dll.name` handler is a forwarder. Entries whose `-arch=` doesn't
+    /// include `machine` are left out. `-ordinal` entries are imported by
+    /// ordinal like `-noname` ones. The import name is left empty.
+    pub fn parse_spec(spec: &str, machine: MachineType) -> Result<ModuleDef, Error> {
+        let mut def = ModuleDef::default();
+        for (index, line) in spec.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let tokens = tokenize(line);
+            if tokens.is_empty() {
+                continue;
+            }
+            let column = line.chars().take_while(|c| c.is_whitespace()).count() + 1;
+            let export =
+                parse_entry(&tokens, machine).map_err(|e| Error::parse(index + 1, column, e))?;
+            if let Some(export) = export {
+                def.exports.push(export);
+            }
+        }
+        Ok(def)
+    }
+}
+
+fn parse_entry(tokens: &[&str], machine: MachineType) -> Result<Option<ShortExport>, String> {
+    let mut tokens = tokens.iter().copied();
+    let ordinal = match tokens.next().unwrap_or_default() {
+        "@" => 0,
+        ordinal => ordinal
+            .parse()
+            .ok()
+            .filter(|&ordinal| ordinal != 0)
+            .ok_or_else(|| format!("invalid ordinal: {}", ordinal))?,
+    };
+    let kind = match tokens.next().unwrap_or_default() {
+        "stdcall" => EntryType::Stdcall,
+        "cdecl" => EntryType::Cdecl,
+        "varargs" => EntryType::Varargs,
+        "thiscall" => EntryType::Thiscall,
+        "fastcall" => EntryType::Fastcall,
+        "extern" => EntryType::Extern,
+        "stub" => EntryType::Stub,
+        "equate" => EntryType::Equate,
+        "" => return Err("expected entry point type".to_string()),
+        kind => return Err(format!("unsupported entry point type: {}", kind)),
+    };
+
+    let mut export = ShortExport {
+        ordinal,
+        data: kind == EntryType::Extern,
+        ..Default::default()
+    };
+    let mut included = true;
+    let name = loop {
+        match tokens.next() {
+            Some("-noname") | Some("-ordinal") => export.no_name = true,
+            Some("-private") => export.private = true,
+            Some("-i386") => included &= arch_matches("i386", machine),
+            Some(flag) if flag.starts_with("-arch=") => {
+                included &= arch_filter(&flag["-arch=".len()..], machine)
+            }
+            // -norelay, -ret64, -register and the like don't affect linking.
+            Some(flag) if flag.starts_with('-') => {}
+            Some(name) if name != "(" && name != ")" => break name,
+            _ => return Err("expected export name".to_string()),
+        }
+    };
+    if export.no_name && ordinal == 0 {
+        return Err(format!("{}: -noname requires an ordinal", name));
+    }
+
+    let mut args_size = None;
+    if matches!(
+        kind,
+        EntryType::Stdcall
+            | EntryType::Cdecl
+            | EntryType::Varargs
+            | EntryType::Thiscall
+            | EntryType::Fastcall
+    ) {
+        if tokens.next() != Some("(") {
+            return Err(format!("{}: expected argument list", name));
+        }
+        let mut size = 0;
+        loop {
+            size += match tokens.next() {
+                Some(")") => break,
+                Some(
+                    "word" | "s_word" | "long" | "float" | "ptr" | "str" | "wstr" | "segptr"
+                    | "segstr",
+                ) => 4,
+                Some("int64" | "double") => 8,
+                Some("int128") => 16,
+                Some(arg) => return Err(format!("{}: unknown argument type: {}", name, arg)),
+                None => return Err(format!("{}: unterminated argument list", name)),
+            };
+        }
+        args_size = Some(size);
+    }
+    let handler = match kind {
+        // The value of an equate isn't a symbol.
+        EntryType::Stub | EntryType::Equate => None,
+        _ => tokens.next(),
+    };
+    if kind == EntryType::Equate {
+        tokens.next();
+    }
+    if let Some(token) = tokens.next() {
+        return Err(format!("{}: unexpected token: {}", name, token));
+    }
+    if !included {
+        return Ok(None);
+    }
+
+    // Decorate the way winebuild writes its .def files.
+    let decorate = |symbol: &str| match (kind, args_size) {
+        (EntryType::Stdcall, Some(size)) if machine == MachineType::I386 => {
+            format!("{}@{}", symbol, size)
+        }
+        (EntryType::Fastcall, Some(size)) if machine == MachineType::I386 => {
+            format!("@{}@{}", symbol, size)
+        }
+        _ => symbol.to_string(),
+    };
+    match handler.and_then(Forward::parse) {
+        Some(forward) => {
+            export.name = handler.unwrap_or_default().to_string();
+            export.ext_name = Some(decorate(name));
+            export.forward = Some(forward);
+        }
+        None => match handler.filter(|&handler| handler != name) {
+            Some(handler) => {
+                export.name = decorate(handler);
+                export.ext_name = Some(decorate(name));
+            }
+            None => export.name = decorate(name),
+        },
+    }
+
+    export.mangle(machine);
+    Ok(Some(export))
+}
+
+/// Whether a comma separated `-arch=` list includes `machine`, entries
+/// starting with `!` exclude
+fn arch_filter(list: &str, machine: MachineType) -> bool {
+    let mut included = None;
+    for arch in list.split(',') {
+        match arch.strip_prefix('!') {
+            Some(arch) if arch_matches(arch, machine) => return false,
+            Some(_) => {}
+            None => included = Some(included.unwrap_or(false) || arch_matches(arch, machine)),
+        }
+    }
+    included.unwrap_or(true)
+}
+
+fn arch_matches(arch: &str, machine: MachineType) -> bool {
+    let names: &[&str] = match machine {
+        MachineType::I386 => &["i386", "win32"],
+        MachineType::ARMNT => &["arm", "win32"],
+        MachineType::AMD64 => &["x86_64", "win64"],
+        MachineType::ARM64 => &["arm64", "win64"],
+        MachineType::ARM64EC => &["arm64ec", "win64"],
+        MachineType::ARM64X => &["arm64", "arm64ec", "win64"],
+    };
+    names.contains(&arch)
+}
+
+/// Split a line at whitespace, parentheses are tokens of their own
+fn tokenize(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, c) in line.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(start) = start.take() {
+                tokens.push(&line[start..index]);
+            }
+            if c == '(' || c == ')' {
+                tokens.push(&line[index..index + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(index);
+        }
+    }
+    if let Some(start) = start {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("@ stdcall -arch=win32 Foo(ptr  long)bar "),
+            [
+                "@",
+                "stdcall",
+                "-arch=win32",
+                "Foo",
+                "(",
+                "ptr",
+                "long",
+                ")",
+                "bar"
+            ]
+        );
+        assert!(tokenize("  \t").is_empty());
+    }
+
+    #[test]
+    fn test_arch_filter() {
+        assert!(arch_filter("win32", MachineType::I386));
+        assert!(arch_filter("win32", MachineType::ARMNT));
+        assert!(!arch_filter("win32", MachineType::AMD64));
+        assert!(arch_filter("i386,x86_64", MachineType::AMD64));
+        assert!(!arch_filter("!i386", MachineType::I386));
+        assert!(arch_filter("!i386", MachineType::ARM64));
+        assert!(arch_filter("arm64ec", MachineType::ARM64X));
+    }
+
+    #[test]
+    fn test_parse_entry_errors() {
+        for (line, err) in [
+            ("0 stdcall Foo()", "invalid ordinal: 0"),
+            ("x stdcall Foo()", "invalid ordinal: x"),
+            ("@", "expected entry point type"),
+            ("@ pascal Foo()", "unsupported entry point type: pascal"),
+            ("@ stdcall -noname", "expected export name"),
+            (
+                "@ stdcall -noname Foo()",
+                "Foo: -noname requires an ordinal",
+            ),
+            ("@ stdcall Foo", "Foo: expected argument list"),
+            ("@ stdcall Foo(ptr", "Foo: unterminated argument list"),
+            ("@ stdcall Foo(bogus)", "Foo: unknown argument type: bogus"),
+            ("@ stdcall Foo() bar baz", "Foo: unexpected token: baz"),
+        ] {
+            assert_eq!(
+                parse_entry(&tokenize(line), MachineType::AMD64).unwrap_err(),
+                err,
+                "{}",
+                line
+            );
+        }
+    }
+}
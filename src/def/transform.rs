@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::{is_decorated, ModuleDef, ShortExport};
+#[cfg(feature = "regex")]
+use crate::Error;
+use crate::{Flavor, MachineType};
+
+/// Export name pattern of a [`Transform`]
+///
+/// Patterns match the name as written in a .def file, without the i386
+/// underscore prefix the parser adds to undecorated names.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Shell style glob, `*` matches any run of characters and `?` any
+    /// single one
+    Glob(String),
+    /// Regular expression, unanchored
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl Pattern {
+    pub fn glob(glob: &str) -> Self {
+        Self::Glob(glob.to_string())
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn regex(regex: &str) -> Result<Self, Error> {
+        regex::Regex::new(regex)
+            .map(Self::Regex)
+            .map_err(|e| Error::validation(e.to_string()))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(glob) => glob_matches(glob.as_bytes(), name.as_bytes()),
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+impl From<&str> for Pattern {
+    fn from(glob: &str) -> Self {
+        Self::glob(glob)
+    }
+}
+
+fn glob_matches(glob: &[u8], name: &[u8]) -> bool {
+    // Backtrack to the last `*` on a mismatch.
+    let (mut g, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some(b'*') => {
+                star = Some((g, n));
+                g += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_g, star_n)) => {
+                    g = star_g + 1;
+                    n = star_n + 1;
+                    star = Some((star_g, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == b'*')
+}
+
+type RenameFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+/// Export filters and renames, applied with [`ModuleDef::transform`] or
+/// [`ImportLibrary::transform`](crate::ImportLibrary::transform)
+///
+/// Exports are kept when they match an include pattern, or there are none,
+/// and no exclude pattern. Kept exports are renamed by the rename map, or
+/// else the rename closure, then get the prefix and suffix. Renamed exports
+/// still import the original name from the DLL. Names are matched and
+/// renamed as written in a .def file, see [`Pattern`].
+#[derive(Clone, Default)]
+pub struct Transform {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    renames: HashMap<String, String>,
+    rename_with: Option<Arc<RenameFn>>,
+    prefix: String,
+    suffix: String,
+    force_data: Vec<Pattern>,
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only exports matching `pattern` or another include pattern
+    pub fn include(mut self, pattern: impl Into<Pattern>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Drop exports matching `pattern`
+    pub fn exclude(mut self, pattern: impl Into<Pattern>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Import `from` as `to`
+    pub fn rename(mut self, from: &str, to: &str) -> Self {
+        self.renames.insert(from.to_string(), to.to_string());
+        self
+    }
+
+    /// Import exports `f` returns a name for under that name
+    pub fn rename_with<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.rename_with = Some(Arc::new(f));
+        self
+    }
+
+    /// Prepend `prefix` to the import names
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Append `suffix` to the import names
+    pub fn suffix(mut self, suffix: &str) -> Self {
+        self.suffix = suffix.to_string();
+        self
+    }
+
+    /// Import exports matching `pattern` as `DATA`
+    pub fn force_data(mut self, pattern: impl Into<Pattern>) -> Self {
+        self.force_data.push(pattern.into());
+        self
+    }
+
+    fn new_name(&self, name: &str) -> String {
+        let renamed = match self.renames.get(name) {
+            Some(renamed) => Some(renamed.clone()),
+            None => self.rename_with.as_ref().and_then(|f| f(name)),
+        };
+        let renamed = renamed.as_deref().unwrap_or(name);
+        // Affixes would break C++ mangled names.
+        if self.prefix.is_empty() && self.suffix.is_empty() || renamed.starts_with('?') {
+            return renamed.to_string();
+        }
+        // Keep stdcall and fastcall decorations at the ends.
+        let (start, rest) = match renamed.strip_prefix('@') {
+            Some(rest) => ("@", rest),
+            None => ("", renamed),
+        };
+        let (base, end) = match rest.rfind('@') {
+            Some(at) if is_decorated(renamed) => rest.split_at(at),
+            _ => (rest, ""),
+        };
+        format!("{}{}{}{}{}", start, self.prefix, base, self.suffix, end)
+    }
+}
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transform")
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("renames", &self.renames)
+            .field("rename_with", &self.rename_with.as_ref().map(|_| ".."))
+            .field("prefix", &self.prefix)
+            .field("suffix", &self.suffix)
+            .field("force_data", &self.force_data)
+            .finish()
+    }
+}
+
+/// What a [`Transform`] changed, by .def file names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransformReport {
+    /// Exports dropped by the include and exclude patterns
+    pub removed: Vec<String>,
+    /// Exports imported under a new name, old and new
+    pub renamed: Vec<(String, String)>,
+    /// Exports turned into `DATA` imports
+    pub forced_data: Vec<String>,
+}
+
+impl TransformReport {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.renamed.is_empty() && self.forced_data.is_empty()
+    }
+
+    pub(crate) fn extend(&mut self, other: TransformReport) {
+        self.removed.extend(other.removed);
+        self.renamed.extend(other.renamed);
+        self.forced_data.extend(other.forced_data);
+    }
+}
+
+/// Formats one change per line as tab separated fields: change, export
+/// name and the new name for renames.
+impl fmt::Display for TransformReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.removed {
+            writeln!(f, "removed\t{}", name)?;
+        }
+        for (old, new) in &self.renamed {
+            writeln!(f, "renamed\t{}\t{}", old, new)?;
+        }
+        for name in &self.forced_data {
+            writeln!(f, "data\t{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl ModuleDef {
+    /// Filter and rename the exports before writing an import library
+    ///
+    /// Renamed exports keep importing the name the `flavor` import library
+    /// would have imported them under.
+    pub fn transform(
+        &mut self,
+        transform: &Transform,
+        machine: MachineType,
+        flavor: Flavor,
+    ) -> TransformReport {
+        let mut report = TransformReport::default();
+        let mut exports = Vec::with_capacity(self.exports.len());
+        for mut export in self.exports.drain(..) {
+            let name = def_name(export.exported_name(), machine).to_string();
+            let included =
+                transform.include.is_empty() || transform.include.iter().any(|p| p.matches(&name));
+            if !included || transform.exclude.iter().any(|p| p.matches(&name)) {
+                report.removed.push(name);
+                continue;
+            }
+            if !export.data && transform.force_data.iter().any(|p| p.matches(&name)) {
+                export.data = true;
+                export.constant = false;
+                report.forced_data.push(name.clone());
+            }
+            let new_name = transform.new_name(&name);
+            if new_name != name {
+                rename(&mut export, &new_name, machine, flavor);
+                report.renamed.push((name, new_name));
+            }
+            exports.push(export);
+        }
+        self.exports = exports;
+        report
+    }
+}
+
+/// Strip the i386 mangling the def parser adds
+fn def_name(name: &str, machine: MachineType) -> &str {
+    match name.strip_prefix('_') {
+        Some(stripped) if machine == MachineType::I386 && !is_decorated(stripped) => stripped,
+        _ => name,
+    }
+}
+
+/// Import `export` as `new_name`, still resolving to the same DLL export
+fn rename(export: &mut ShortExport, new_name: &str, machine: MachineType, flavor: Flavor) {
+    if !export.no_name {
+        export.export_as = Some(export.import_name(machine, flavor));
+    }
+    export.name = if machine == MachineType::I386 && !is_decorated(new_name) {
+        format!("_{}", new_name)
+    } else {
+        new_name.to_string()
+    };
+    export.ext_name = None;
+    export.symbol_name.clear();
+    export.alias_target.clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        let glob = |glob: &str, name: &str| Pattern::glob(glob).matches(name);
+        assert!(glob("*", ""));
+        assert!(glob("Py*", "Py_Initialize"));
+        assert!(!glob("Py*", "_Py_Initialize"));
+        assert!(glob("*Free*", "PyObject_Free"));
+        assert!(glob("Py?_*", "PyA_x"));
+        assert!(!glob("Py?_*", "Py_x"));
+        assert!(glob("a*b*c", "aXbYbZc"));
+        assert!(!glob("a*b*c", "aXbYbZ"));
+        assert!(glob("foo@*", "foo@12"));
+    }
+
+    #[test]
+    fn test_new_name() {
+        let transform = Transform::new()
+            .rename("foo", "bar")
+            .rename_with(|name| name.strip_prefix("old_").map(|n| format!("new_{}", n)))
+            .prefix("vnd_")
+            .suffix("_2");
+        assert_eq!(transform.new_name("foo"), "vnd_bar_2");
+        assert_eq!(transform.new_name("old_x"), "vnd_new_x_2");
+        assert_eq!(transform.new_name("baz"), "vnd_baz_2");
+        assert_eq!(transform.new_name("baz@8"), "vnd_baz_2@8");
+        assert_eq!(transform.new_name("@fast@4"), "@vnd_fast_2@4");
+        assert_eq!(transform.new_name("?f@@YAXXZ"), "?f@@YAXXZ");
+    }
+
+    #[test]
+    fn test_import_name() {
+        let mut def = ModuleDef::parse(
+            "EXPORTS\nfoo\nbar@4\n_baz@4\nqux = quux\nold == new\nx\n",
+            MachineType::I386,
+        )
+        .unwrap();
+        def.exports[5].export_as = Some("y".to_string());
+        let names = |flavor| -> Vec<_> {
+            def.exports
+                .iter()
+                .map(|e| e.import_name(MachineType::I386, flavor))
+                .collect()
+        };
+        assert_eq!(
+            names(Flavor::Msvc),
+            ["foo", "bar@4", "_baz@4", "qux", "new", "y"]
+        );
+        assert_eq!(
+            names(Flavor::Gnu),
+            ["foo", "bar@4", "baz@4", "qux", "old", "y"]
+        );
+
+        let def = ModuleDef::parse(
+            "EXPORTS\nfoo\n#bar\n?baz@@$$hYAXXZ\nqux DATA\n",
+            MachineType::ARM64EC,
+        )
+        .unwrap();
+        let names: Vec<_> = def
+            .exports
+            .iter()
+            .map(|e| e.import_name(MachineType::ARM64EC, Flavor::Msvc))
+            .collect();
+        assert_eq!(names, ["foo", "bar", "?baz@@YAXXZ", "qux"]);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+
+use crate::def::{ModuleDef, ShortExport};
+use crate::{Error, MachineType};
+
+/// Size of `IMAGE_EXPORT_DIRECTORY`
+const DIRECTORY_SIZE: u32 = 40;
+
+/// An export with its ordinal assigned and its name undecorated
+#[derive(Debug)]
+pub(crate) struct Export<'a> {
+    pub name: Option<&'a str>,
+    pub ordinal: u16,
+    pub target: Target<'a>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Target<'a> {
+    /// Resolved to the address of the export's symbol
+    Symbol(&'a ShortExport),
+    /// Forwarded to another DLL, e.g. "other.bar" or "other.#7"
+    Forward(String),
+}
+
+/// `.edata` contents laid out relative to the start of the section
+#[derive(Debug)]
+pub(crate) struct ExportDirectory<'a> {
+    pub data: Vec<u8>,
+    /// Offsets of the address table entries to resolve to exported symbols
+    pub symbols: Vec<(u32, &'a ShortExport)>,
+    /// Offsets of the fields holding an offset into the section, which
+    /// need the section's address added
+    pub internal: Vec<u32>,
+}
+
+/// Collect the exports of a module definition with ordinals assigned, in
+/// ordinal order
+pub(crate) fn exports(def: &ModuleDef, machine: MachineType) -> Result<Vec<Export<'_>>, Error> {
+    let mut names = HashSet::new();
+    let mut ordinals = HashSet::new();
+    for export in &def.exports {
+        if !names.insert(export.exported_name()) {
+            return Err(Error::validation(format!(
+                "duplicate export: {}",
+                export.exported_name()
+            )));
+        }
+        if export.ordinal != 0 && !ordinals.insert(export.ordinal) {
+            return Err(Error::validation(format!(
+                "duplicate ordinal: {}",
+                export.ordinal
+            )));
+        }
+    }
+
+    // Like link.exe, exports without an ordinal are numbered after the
+    // highest assigned one.
+    let mut next_ordinal = ordinals.iter().copied().max().unwrap_or(0);
+    let mut exports = Vec::with_capacity(def.exports.len());
+    for export in &def.exports {
+        let ordinal = if export.ordinal != 0 {
+            export.ordinal
+        } else {
+            next_ordinal = next_ordinal
+                .checked_add(1)
+                .ok_or_else(|| Error::validation("too many exports for ordinals"))?;
+            next_ordinal
+        };
+        let target = match &export.forward {
+            Some(forward) => Target::Forward(forward.to_string()),
+            None => Target::Symbol(export),
+        };
+        exports.push(Export {
+            name: (!export.no_name).then(|| undecorate(export.exported_name(), machine)),
+            ordinal,
+            target,
+        });
+    }
+    exports.sort_by_key(|export| export.ordinal);
+    Ok(exports)
+}
+
+/// Remove i386 mangling added by the def parser
+pub(crate) fn undecorate(name: &str, machine: MachineType) -> &str {
+    match machine {
+        MachineType::I386 => name.strip_prefix('_').unwrap_or(name),
+        _ => name,
+    }
+}
+
+/// Lay out the export directory, address, name pointer and ordinal tables
+/// followed by the strings they refer to
+pub(crate) fn build<'a>(import_name: &str, exports: &[Export<'a>]) -> ExportDirectory<'a> {
+    let (base, function_count) = match (exports.first(), exports.last()) {
+        (Some(first), Some(last)) => (first.ordinal, u32::from(last.ordinal - first.ordinal) + 1),
+        _ => (1, 0),
+    };
+    let mut names: Vec<(&str, u16)> = exports
+        .iter()
+        .filter_map(|export| export.name.map(|name| (name, export.ordinal - base)))
+        .collect();
+    // The loader binary searches the name table.
+    names.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+    let functions_offset = DIRECTORY_SIZE;
+    let names_offset = functions_offset + 4 * function_count;
+    let ordinals_offset = names_offset + 4 * names.len() as u32;
+    let strings_offset = ordinals_offset + 2 * names.len() as u32;
+
+    let mut strings = Vec::new();
+    let mut add_string = |s: &str| {
+        let offset = strings_offset + strings.len() as u32;
+        strings.extend_from_slice(s.as_bytes());
+        strings.push(0);
+        offset
+    };
+    let import_name_offset = add_string(import_name);
+
+    let mut directory = ExportDirectory {
+        data: Vec::new(),
+        symbols: Vec::new(),
+        internal: vec![12, 28, 32, 36],
+    };
+    let mut address_table = vec![0u32; function_count as usize];
+    for export in exports {
+        let index = u32::from(export.ordinal - base);
+        let offset = functions_offset + 4 * index;
+        match &export.target {
+            Target::Symbol(symbol) => directory.symbols.push((offset, symbol)),
+            Target::Forward(forward) => {
+                address_table[index as usize] = add_string(forward);
+                directory.internal.push(offset);
+            }
+        }
+    }
+    let name_offsets: Vec<u32> = names.iter().map(|(name, _)| add_string(name)).collect();
+
+    let data = &mut directory.data;
+    // IMAGE_EXPORT_DIRECTORY
+    for field in [
+        0,
+        0,
+        0,
+        import_name_offset,
+        u32::from(base),
+        function_count,
+        names.len() as u32,
+        functions_offset,
+        names_offset,
+        ordinals_offset,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    for address in address_table {
+        data.extend_from_slice(&address.to_le_bytes());
+    }
+    for (i, name_offset) in name_offsets.into_iter().enumerate() {
+        directory.internal.push(names_offset + 4 * i as u32);
+        data.extend_from_slice(&name_offset.to_le_bytes());
+    }
+    for (_, index) in &names {
+        data.extend_from_slice(&index.to_le_bytes());
+    }
+    data.extend_from_slice(&strings);
+    directory
+}
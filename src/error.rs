@@ -0,0 +1,116 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use crate::Limit;
+
+/// Errors of this crate
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Malformed input text, such as a .def or .spec file, located by 1-based
+    /// line and column
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// Input that parses but can't be turned into an import library, such
+    /// as a null byte in a name or conflicting exports
+    Validation(String),
+    /// Feature, machine type or flavor not supported, or disabled by cargo
+    /// features
+    Unsupported(String),
+    /// Input or output exceeding one of the configured [`Limits`](crate::Limits)
+    LimitExceeded { limit: Limit, max: usize },
+    /// Object file or archive that could not be read or written
+    Encoding(Box<dyn StdError + Send + Sync>),
+    /// I/O error of the underlying reader or writer
+    Io(io::Error),
+}
+
+impl Error {
+    pub(crate) fn parse(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self::Parse {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn validation(message: impl Into<String>) -> Self {
+        Self::Validation(message.into())
+    }
+
+    pub(crate) fn encoding(err: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
+        Self::Encoding(err.into())
+    }
+
+    /// The closest [`io::ErrorKind`], as used by the `io::Error` conversion
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Self::Parse { .. } | Self::Validation(_) | Self::LimitExceeded { .. } => {
+                io::ErrorKind::InvalidInput
+            }
+            Self::Unsupported(_) => io::ErrorKind::Unsupported,
+            Self::Encoding(_) => io::ErrorKind::InvalidData,
+            Self::Io(err) => err.kind(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            Self::Validation(message) | Self::Unsupported(message) => f.write_str(message),
+            Self::LimitExceeded { limit, max } => {
+                write!(f, "{} exceeds the limit of {}", limit, max)
+            }
+            Self::Encoding(err) => err.fmt(f),
+            Self::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Encoding(err) => Some(err.as_ref()),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<object::read::Error> for Error {
+    fn from(err: object::read::Error) -> Self {
+        Self::encoding(err)
+    }
+}
+
+#[cfg(any(feature = "gnu", feature = "stub", feature = "exp"))]
+impl From<object::write::Error> for Error {
+    fn from(err: object::write::Error) -> Self {
+        Self::encoding(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use object::pe::*;
+use object::write::{Mangling, Object, Relocation, Symbol, SymbolSection};
+use object::{
+    BinaryFormat, Endianness, SectionFlags, SectionKind, SubArchitecture, SymbolFlags, SymbolKind,
+    SymbolScope,
+};
+
+use crate::def::ModuleDef;
+use crate::edata;
+use crate::{Error, MachineType};
+
+/// Export object (.exp) generator
+///
+/// Produces the COFF object `lib.exe /DEF:` writes next to the import
+/// library. It holds the `.edata` export directory with relocations against
+/// the exported symbols, so linking it into a DLL exports them without the
+/// module definition.
+#[derive(Debug, Clone)]
+pub struct ExportObject {
+    def: ModuleDef,
+    machine: MachineType,
+}
+
+impl ExportObject {
+    /// Create new export object generator from module definition text content
+    pub fn new(def: &str, machine: MachineType) -> Result<Self, Error> {
+        let def = ModuleDef::parse(def, machine)?;
+        Ok(Self::from_def(def, machine))
+    }
+
+    /// Create new export object generator from `ModuleDef`
+    pub fn from_def(def: ModuleDef, machine: MachineType) -> Self {
+        ExportObject { def, machine }
+    }
+
+    /// Write out the export object
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let exports = edata::exports(&self.def, self.machine)?;
+        let directory = edata::build(&self.def.import_name, &exports);
+
+        let mut obj = Object::new(
+            BinaryFormat::Coff,
+            self.machine.to_arch(),
+            Endianness::Little,
+        );
+        if self.machine == MachineType::ARM64EC {
+            obj.set_sub_architecture(Some(SubArchitecture::Arm64EC));
+        }
+        // Symbol names are already mangled by the def parser.
+        obj.mangling = Mangling::None;
+
+        let edata_sec = obj.add_section(Vec::new(), b".edata".to_vec(), SectionKind::ReadOnlyData);
+        obj.section_mut(edata_sec).flags = SectionFlags::Coff {
+            characteristics: IMAGE_SCN_ALIGN_4BYTES
+                | IMAGE_SCN_CNT_INITIALIZED_DATA
+                | IMAGE_SCN_MEM_READ,
+        };
+        obj.append_section_data(edata_sec, &directory.data, 4);
+
+        let img_rel = self.machine.img_rel_relocation();
+        let edata_sym = obj.section_symbol(edata_sec);
+        for &offset in &directory.internal {
+            obj.add_relocation(
+                edata_sec,
+                Relocation {
+                    offset: offset.into(),
+                    symbol: edata_sym,
+                    addend: 0,
+                    flags: object::RelocationFlags::Coff { typ: img_rel },
+                },
+            )?;
+        }
+
+        // Several exports may refer to the same symbol, "foo = bar" and
+        // "baz = bar" both resolve to bar.
+        let mut symbols = HashMap::new();
+        for &(offset, export) in &directory.symbols {
+            let symbol = *symbols.entry(export.name.as_str()).or_insert_with(|| {
+                obj.add_symbol(Symbol {
+                    name: export.name.as_bytes().to_vec(),
+                    value: 0,
+                    size: 0,
+                    kind: if export.data || export.constant {
+                        SymbolKind::Data
+                    } else {
+                        SymbolKind::Text
+                    },
+                    scope: SymbolScope::Dynamic,
+                    weak: false,
+                    section: SymbolSection::Undefined,
+                    flags: SymbolFlags::None,
+                })
+            });
+            obj.add_relocation(
+                edata_sec,
+                Relocation {
+                    offset: offset.into(),
+                    symbol,
+                    addend: 0,
+                    flags: object::RelocationFlags::Coff { typ: img_rel },
+                },
+            )?;
+        }
+
+        let data = obj.write()?;
+        writer.write_all(&data)?;
+        Ok(())
+    }
+}
@@ -1,17 +1,23 @@
-use std::io::{Error, ErrorKind, Seek, Write};
+use std::collections::HashSet;
+use std::io::{Seek, Write};
+use std::mem;
 
 use object::pe::*;
+use object::read::archive::ArchiveFile;
+use object::read::coff::CoffFile;
+use object::read::{Object as _, ObjectSection as _, ObjectSymbol as _};
 use object::write::{Mangling, Object, Relocation, Symbol, SymbolId, SymbolSection};
 use object::{
-    BinaryFormat, Endianness, SectionFlags, SectionKind, SymbolFlags, SymbolKind, SymbolScope,
+    BinaryFormat, Endianness, LittleEndian as LE, SectionFlags, SectionKind, SymbolFlags,
+    SymbolKind, SymbolScope,
 };
 
-use ar_archive_writer::{
-    write_archive_to_stream, ArchiveKind, NewArchiveMember, DEFAULT_OBJECT_READER,
-};
+use ar_archive_writer::{write_archive_to_stream, ArchiveKind, NewArchiveMember};
 
 use crate::def::{ModuleDef, ShortExport};
-use crate::{ArchiveMember, MachineType};
+use crate::{
+    ArchiveMember, ArchiveWriter, Error, Flavor, GnuMemberNames, MachineType, WriteOptions,
+};
 
 const JMP_IX86_BYTES: [u8; 8] = [0xff, 0x25, 0x00, 0x00, 0x00, 0x00, 0x90, 0x90];
 // On i386, `ff 25 disp32` is `jmp dword ptr [disp32]` — an absolute
@@ -38,18 +44,6 @@ const ARM64_RELOCATIONS: [(u64, i64, u16); 2] = [
     (4, 0, IMAGE_REL_ARM64_PAGEOFFSET_12L),
 ];
 
-impl MachineType {
-    fn to_arch(self) -> object::Architecture {
-        use object::Architecture::*;
-        match self {
-            Self::AMD64 => X86_64,
-            Self::ARMNT => Arm,
-            Self::ARM64 | Self::ARM64EC | Self::ARM64X => Aarch64,
-            Self::I386 => I386,
-        }
-    }
-}
-
 /// GNU flavored Windows import library generator
 #[derive(Debug, Clone)]
 pub struct GnuImportLibrary {
@@ -74,47 +68,307 @@ impl GnuImportLibrary {
     }
 
     /// Write out the import library
-    pub fn write_to<W: Write + Seek>(&self, writer: &mut W) -> Result<(), Error> {
-        let mut members = Vec::new();
-        let mut factory = ObjectFactory::new(&self.def.import_name, self.machine)?;
-        for export in &self.def.exports {
-            members.push(factory.make_one(export)?);
+    pub fn write_to<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> Result<(), Error> {
+        write_members(writer, self.members(options)?, options)
+    }
+
+    /// Generate the archive members of the import library
+    fn members(&self, options: &WriteOptions) -> Result<Vec<ArchiveMember>, Error> {
+        let factory =
+            ObjectFactory::new(&self.def.import_name, self.machine)?.with_options(options)?;
+        let mut members = self.make_exports(&MemberTemplates::new(&factory)?)?;
+        if options.gnu_member_names == GnuMemberNames::ExportName {
+            let names = factory.export_member_names(&self.def.exports);
+            for (member, name) in members.iter_mut().zip(names) {
+                member.name = name;
+            }
         }
         members.push(factory.make_head()?);
         members.push(factory.make_tail()?);
         members.reverse();
+        Ok(members)
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn make_exports(&self, templates: &MemberTemplates) -> Result<Vec<ArchiveMember>, Error> {
+        let exports = self.def.exports.iter().enumerate();
+        exports
+            .map(|(seq, export)| templates.make_one(export, seq))
+            .collect()
+    }
 
-        let archive_members: Vec<NewArchiveMember> = members
+    /// Generate the export members in parallel, numbered as in the serial
+    /// path so the archive is the same
+    #[cfg(feature = "rayon")]
+    fn make_exports(&self, templates: &MemberTemplates) -> Result<Vec<ArchiveMember>, Error> {
+        use rayon::prelude::*;
+
+        let exports = self.def.exports.par_iter().enumerate();
+        exports
+            .map(|(seq, export)| templates.make_one(export, seq))
+            .collect()
+    }
+}
+
+/// Write out an import library bundling the imports of several DLLs
+pub fn write_umbrella_to<W: Write + Seek>(
+    defs: Vec<ModuleDef>,
+    machine: MachineType,
+    writer: &mut W,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    if options.gnu_archive_name.is_some()
+        || options.gnu_member_prefix.is_some()
+        || options.gnu_symbol_stem.is_some()
+    {
+        return Err(Error::validation(
+            "GNU naming options can't be shared by several DLLs",
+        ));
+    }
+    let mut members = Vec::new();
+    for def in defs {
+        members.extend(GnuImportLibrary::new(def, machine).members(options)?);
+    }
+    let mut names = HashSet::new();
+    for member in &members {
+        if !names.insert(member.name.as_str()) {
+            return Err(Error::validation(format!(
+                "duplicate archive member name: {}",
+                member.name
+            )));
+        }
+    }
+    write_members(writer, members, options)
+}
+
+/// Rewrite a GNU import library to import from `dll_name`
+///
+/// The head and tail members are regenerated and the import members are
+/// patched to reference the new head symbol, other members are kept as is.
+pub fn retarget_to<W: Write + Seek>(
+    data: &[u8],
+    dll_name: &str,
+    writer: &mut W,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    let archive = ArchiveFile::parse(data)?;
+    let mut members = Vec::new();
+    for member in archive.members() {
+        let member = member?;
+        if member.name().starts_with(b"/") {
+            continue;
+        }
+        let member_data = member.data(data)?;
+        members.push(ArchiveMember {
+            name: String::from_utf8_lossy(member.name()).into_owned(),
+            data: member_data.to_vec(),
+        });
+    }
+
+    // The head member defines `_head_<dll>`, mangled on i386.
+    let mut head = None;
+    for (index, member) in members.iter().enumerate() {
+        let Some((machine, symbols)) = coff_symbols(&member.data) else {
+            continue;
+        };
+        let prefix = if machine == MachineType::I386 {
+            "__head_"
+        } else {
+            "_head_"
+        };
+        let Some((name, _)) = symbols
             .into_iter()
-            .map(|m| NewArchiveMember::new(m.data, &DEFAULT_OBJECT_READER, m.name))
-            .collect();
-        write_archive_to_stream(writer, &archive_members, ArchiveKind::Gnu, false, None)
+            .find(|(name, defined)| *defined && name.starts_with(prefix))
+        else {
+            continue;
+        };
+        if head.is_some() {
+            return Err(Error::validation("import library references several DLLs"));
+        }
+        let stem = name[prefix.len()..].to_string();
+        head = Some((index, machine, name, stem));
     }
+    let (head_index, machine, old_head, old_stem) =
+        head.ok_or_else(|| Error::validation("no import head found"))?;
+
+    let factory = ObjectFactory::new(dll_name, machine)?.with_options(options)?;
+    let new_head = format!(
+        "{}{}",
+        &old_head[..old_head.len() - old_stem.len()],
+        factory.symbol_stem
+    );
+    let old_iname = format!("{}_iname", old_stem);
+    // The per-export members share the prefix of the head member, whatever
+    // naming the library was written with.
+    let old_member_prefix = members[head_index]
+        .name
+        .strip_suffix("h.o")
+        .map(str::to_string);
+    for (index, member) in members.iter_mut().enumerate() {
+        if index == head_index {
+            *member = factory.make_head()?;
+            continue;
+        }
+        let Some((_, symbols)) = coff_symbols(&member.data) else {
+            continue;
+        };
+        if symbols
+            .iter()
+            .any(|(name, defined)| *defined && name.ends_with(&old_iname))
+        {
+            *member = factory.make_tail()?;
+        } else if symbols
+            .iter()
+            .any(|(name, defined)| !*defined && *name == old_head)
+        {
+            member.data = rename_symbol(&member.data, &old_head, &new_head)?;
+            let rest = old_member_prefix
+                .as_deref()
+                .and_then(|prefix| member.name.strip_prefix(prefix));
+            if let Some(rest) = rest {
+                member.name = format!("{}{}", factory.member_prefix, rest);
+            }
+        }
+    }
+    write_members(writer, members, options)
+}
+
+/// Machine and global symbols of a COFF object, with whether each is defined
+fn coff_symbols(data: &[u8]) -> Option<(MachineType, Vec<(String, bool)>)> {
+    let file = object::File::parse(data).ok()?;
+    if file.format() != BinaryFormat::Coff {
+        return None;
+    }
+    let machine = MachineType::from_u16(u16::from_le_bytes([data[0], data[1]]))?;
+    let symbols = file
+        .symbols()
+        .filter(|sym| sym.is_global())
+        .filter_map(|sym| Some((sym.name().ok()?.to_string(), !sym.is_undefined())))
+        .collect();
+    Some((machine, symbols))
+}
+
+/// Rename the symbol `from` of a COFF object to `to`
+///
+/// Long names are appended to the string table, which must end the file.
+fn rename_symbol(data: &[u8], from: &str, to: &str) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::encoding("invalid COFF object");
+    let read_u32 = |offset: usize| -> Result<usize, Error> {
+        let bytes = data.get(offset..offset + 4).ok_or_else(invalid)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    let symtab = read_u32(8)?;
+    let nsyms = read_u32(12)?;
+    let strtab = symtab + nsyms * 18;
+    let strtab_size = read_u32(strtab)?;
+    if strtab + strtab_size != data.len() {
+        return Err(invalid());
+    }
+    let name_of = |entry: &[u8]| -> Result<Vec<u8>, Error> {
+        if entry[..4] == [0; 4] {
+            let offset = strtab + u32::from_le_bytes(entry[4..8].try_into().unwrap()) as usize;
+            let bytes = data.get(offset..).ok_or_else(invalid)?;
+            let end = memchr::memchr(0, bytes).ok_or_else(invalid)?;
+            Ok(bytes[..end].to_vec())
+        } else {
+            let end = memchr::memchr(0, &entry[..8]).unwrap_or(8);
+            Ok(entry[..end].to_vec())
+        }
+    };
+
+    let mut patched = data.to_vec();
+    let mut index = 0;
+    while index < nsyms {
+        let offset = symtab + index * 18;
+        let entry = data.get(offset..offset + 18).ok_or_else(invalid)?;
+        if name_of(entry)? == from.as_bytes() {
+            let mut name = [0; 8];
+            if to.len() <= 8 {
+                name[..to.len()].copy_from_slice(to.as_bytes());
+            } else {
+                let str_offset = (patched.len() - strtab) as u32;
+                name[4..].copy_from_slice(&str_offset.to_le_bytes());
+                patched.extend_from_slice(to.as_bytes());
+                patched.push(0);
+            }
+            patched[offset..offset + 8].copy_from_slice(&name);
+        }
+        // Skip the auxiliary entries
+        index += 1 + entry[17] as usize;
+    }
+    let strtab_size = (patched.len() - strtab) as u32;
+    patched[strtab..strtab + 4].copy_from_slice(&strtab_size.to_le_bytes());
+    Ok(patched)
+}
+
+fn write_members<W: Write + Seek>(
+    writer: &mut W,
+    members: Vec<ArchiveMember>,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    let archive_members: Vec<NewArchiveMember> = members
+        .into_iter()
+        .map(|m| options.archive_member(m.data, m.name))
+        .collect();
+    let mut writer = ArchiveWriter::new(writer);
+    let result =
+        write_archive_to_stream(&mut writer, &archive_members, ArchiveKind::Gnu, false, None);
+    writer.finish(result)
 }
 
 #[derive(Debug)]
 struct ObjectFactory<'a> {
     machine: MachineType,
     import_name: &'a str,
-    output_name: String,
-    seq: usize,
+    /// Prefix of the member names
+    member_prefix: String,
+    /// Stem of the head and iname symbols
+    symbol_stem: String,
 }
 
 impl<'a> ObjectFactory<'a> {
     fn new(import_name: &'a str, machine: MachineType) -> Result<Self, Error> {
         if import_name.contains('\0') {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
+            return Err(Error::validation(
                 "import name contains null byte".to_string(),
             ));
         }
         Ok(Self {
             machine,
             import_name,
-            output_name: format!("{}.a", import_name),
-            seq: 0,
+            member_prefix: format!("{}_a_", import_name.replace('.', "_")),
+            symbol_stem: import_name.replace('.', "_"),
         })
     }
+
+    /// Apply the GNU naming options
+    fn with_options(mut self, options: &WriteOptions) -> Result<Self, Error> {
+        if let Some(prefix) = &options.gnu_member_prefix {
+            if prefix.contains(['/', '\0']) {
+                return Err(Error::validation(format!(
+                    "invalid member prefix: {:?}",
+                    prefix
+                )));
+            }
+            self.member_prefix = prefix.clone();
+        } else if let Some(archive_name) = &options.gnu_archive_name {
+            self.member_prefix = format!("{}_", label(archive_name));
+        }
+        if let Some(stem) = &options.gnu_symbol_stem {
+            if stem.is_empty() || stem.contains('\0') {
+                return Err(Error::validation(format!(
+                    "invalid symbol stem: {:?}",
+                    stem
+                )));
+            }
+            self.symbol_stem = stem.clone();
+        }
+        Ok(self)
+    }
     fn make_relocation(
         &self,
         offset: u64,
@@ -179,15 +433,11 @@ impl<'a> ObjectFactory<'a> {
         let id5_sym = obj.section_symbol(id5);
         let id4_sym = obj.section_symbol(id4);
         let img_rel = self.machine.img_rel_relocation();
-        obj.add_relocation(id2, self.make_relocation(0, id4_sym, 0, img_rel))
-            .map_err(|e| Error::other(e.to_string()))?;
-        obj.add_relocation(id2, self.make_relocation(16, id5_sym, 0, img_rel))
-            .map_err(|e| Error::other(e.to_string()))?;
-
-        let import_name = self.import_name.replace('.', "_");
+        obj.add_relocation(id2, self.make_relocation(0, id4_sym, 0, img_rel))?;
+        obj.add_relocation(id2, self.make_relocation(16, id5_sym, 0, img_rel))?;
 
         let head_sym = Symbol {
-            name: format!("_head_{}", import_name).into_bytes(),
+            name: format!("_head_{}", self.symbol_stem).into_bytes(),
             value: 0,
             size: 0,
             kind: SymbolKind::Data,
@@ -199,7 +449,7 @@ impl<'a> ObjectFactory<'a> {
         obj.add_symbol(head_sym);
 
         let iname_sym = Symbol {
-            name: format!("{}_iname", import_name).into_bytes(),
+            name: format!("{}_iname", self.symbol_stem).into_bytes(),
             value: 0,
             size: 0,
             kind: SymbolKind::Data,
@@ -211,11 +461,10 @@ impl<'a> ObjectFactory<'a> {
         let iname_sym_id = obj.add_symbol(iname_sym);
 
         obj.append_section_data(id2, &[0; 20], 4);
-        obj.add_relocation(id2, self.make_relocation(12, iname_sym_id, 0, img_rel))
-            .map_err(|e| Error::other(e.to_string()))?;
+        obj.add_relocation(id2, self.make_relocation(12, iname_sym_id, 0, img_rel))?;
         Ok(ArchiveMember {
-            name: format!("{}_h.o", self.output_name.replace('.', "_")),
-            data: obj.write().map_err(|e| Error::other(e.to_string()))?,
+            name: format!("{}h.o", self.member_prefix),
+            data: obj.write()?,
         })
     }
 
@@ -273,9 +522,8 @@ impl<'a> ObjectFactory<'a> {
 
         obj.add_file_symbol(b"fake".to_vec());
 
-        let import_name = self.import_name.replace('.', "_");
         let iname_sym = Symbol {
-            name: format!("{}_iname", import_name).into_bytes(),
+            name: format!("{}_iname", self.symbol_stem).into_bytes(),
             value: 0,
             size: 0,
             kind: SymbolKind::Data,
@@ -294,18 +542,16 @@ impl<'a> ObjectFactory<'a> {
         obj.append_section_data(id7, &import_name_bytes, 4);
 
         Ok(ArchiveMember {
-            name: format!("{}_t.o", self.output_name.replace('.', "_")),
-            data: obj.write().map_err(|e| Error::other(e.to_string()))?,
+            name: format!("{}t.o", self.member_prefix),
+            data: obj.write()?,
         })
     }
 
-    fn make_one(&mut self, export: &ShortExport) -> Result<ArchiveMember, Error> {
-        if export.name.contains('\0') {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                "export name contains null byte".to_string(),
-            ));
-        }
+    /// Generate the import member of `export`, `seq` numbers the member name
+    ///
+    /// [`MemberTemplates`] generates the same members faster.
+    fn make_one(&self, export: &ShortExport, seq: usize) -> Result<ArchiveMember, Error> {
+        check_export_name(export)?;
 
         let mut obj = Object::new(
             BinaryFormat::Coff,
@@ -366,9 +612,8 @@ impl<'a> ObjectFactory<'a> {
                 | IMAGE_SCN_MEM_WRITE,
         };
 
-        let import_name = self.import_name.replace('.', "_");
         let head_sym = Symbol {
-            name: format!("_head_{}", import_name).into_bytes(),
+            name: format!("_head_{}", self.symbol_stem).into_bytes(),
             value: 0,
             size: 0,
             kind: SymbolKind::Data,
@@ -421,16 +666,14 @@ impl<'a> ObjectFactory<'a> {
                 obj.add_relocation(
                     text_sec,
                     self.make_relocation(offset, exp_imp_sym, addend, kind),
-                )
-                .map_err(|e| Error::other(e.to_string()))?;
+                )?;
             }
         }
 
         let img_rel = self.machine.img_rel_relocation();
 
         obj.append_section_data(id7, &[0; 4], 4);
-        obj.add_relocation(id7, self.make_relocation(0, head_sym, 0, img_rel))
-            .map_err(|e| Error::other(e.to_string()))?;
+        obj.add_relocation(id7, self.make_relocation(0, head_sym, 0, img_rel))?;
 
         let id6_sym = obj.section_symbol(id6);
         let id5_data = if export.no_name {
@@ -445,8 +688,7 @@ impl<'a> ObjectFactory<'a> {
                 0x80,
             ]
         } else {
-            obj.add_relocation(id5, self.make_relocation(0, id6_sym, 0, img_rel))
-                .map_err(|e| Error::other(e.to_string()))?;
+            obj.add_relocation(id5, self.make_relocation(0, id6_sym, 0, img_rel))?;
             [0; 8]
         };
         obj.append_section_data(id5, &id5_data, 4);
@@ -463,37 +705,339 @@ impl<'a> ObjectFactory<'a> {
                 0x80,
             ]
         } else {
-            obj.add_relocation(id4, self.make_relocation(0, id6_sym, 0, img_rel))
-                .map_err(|e| Error::other(e.to_string()))?;
+            obj.add_relocation(id4, self.make_relocation(0, id6_sym, 0, img_rel))?;
             [0; 8]
         };
         obj.append_section_data(id4, &id4_data, 4);
 
         if !export.no_name {
-            // Remove i386 mangling added by the def parser.
-            let export_name = match self.machine {
-                MachineType::I386 => export.name.strip_prefix("_").unwrap(),
-                _ => &export.name,
+            obj.append_section_data(id6, &self.hint_name(export), 2);
+        }
+
+        Ok(ArchiveMember {
+            name: self.member_name(seq),
+            data: obj.write()?,
+        })
+    }
+
+    /// The `.idata$6` contents of a named import: the ordinal hint and the
+    /// null terminated name
+    fn hint_name(&self, export: &ShortExport) -> Vec<u8> {
+        let export_name = export.import_name(self.machine, Flavor::Gnu);
+        let mut data = Vec::with_capacity(2 + export_name.len() + 1);
+        data.extend_from_slice(&export.ordinal.to_le_bytes());
+        data.extend_from_slice(export_name.as_bytes());
+        data.push(0);
+        data
+    }
+
+    fn member_name(&self, seq: usize) -> String {
+        format!("{}s{:05}.o", self.member_prefix, seq)
+    }
+
+    /// Member names of [`GnuMemberNames::ExportName`], unique among the
+    /// members of the library
+    fn export_member_names(&self, exports: &[ShortExport]) -> Vec<String> {
+        let prefix = &self.member_prefix;
+        let mut taken = HashSet::new();
+        exports
+            .iter()
+            .map(|export| {
+                // Keep names portable, GNU archives can't have `/` in them.
+                let name = label(&export.name);
+                let mut member_name = format!("{}e_{}.o", prefix, name);
+                let mut suffix = 1;
+                while !taken.insert(member_name.clone()) {
+                    member_name = format!("{}e_{}_{}.o", prefix, name, suffix);
+                    suffix += 1;
+                }
+                member_name
+            })
+            .collect()
+    }
+}
+
+/// Replace every character but ASCII letters and digits by `_`, like
+/// dlltool does for labels
+fn label(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn check_export_name(export: &ShortExport) -> Result<(), Error> {
+    if export.name.is_empty() {
+        return Err(Error::validation("empty export name"));
+    }
+    if export.name.contains('\0') {
+        return Err(Error::validation(
+            "export name contains null byte".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Build the export members of `def` one after the other with
+/// [`ObjectFactory::make_one`], or from [`MemberTemplates`] when `templates`
+/// is set, returning their total size
+///
+/// Only exposed for the benchmark comparing the two.
+#[doc(hidden)]
+pub fn bench_export_members(
+    def: &ModuleDef,
+    machine: MachineType,
+    templates: bool,
+) -> Result<usize, Error> {
+    let factory = ObjectFactory::new(&def.import_name, machine)?;
+    let member_templates = MemberTemplates::new(&factory)?;
+    let mut size = 0;
+    for (seq, export) in def.exports.iter().enumerate() {
+        let member = if templates {
+            member_templates.make_one(export, seq)?
+        } else {
+            factory.make_one(export, seq)?
+        };
+        size += member.data.len();
+    }
+    Ok(size)
+}
+
+/// Import members patched from templates instead of laid out by
+/// `object::write` for every export
+///
+/// Members of one DLL only differ in the symbol names, the `.idata$6` hint
+/// and name and the ordinals. Everything before the `.idata$6` data only
+/// depends on whether the export is data and whether it is imported by
+/// ordinal, so a template is generated by [`ObjectFactory::make_one`] for
+/// each combination. The offsets to patch are read back from the templates.
+struct MemberTemplates<'a> {
+    factory: &'a ObjectFactory<'a>,
+    head_name: Vec<u8>,
+    /// Indexed by `data as usize * 2 + no_name as usize`
+    templates: [MemberTemplate; 4],
+}
+
+struct MemberTemplate {
+    /// File header, section headers, section data and relocations up to the
+    /// `.idata$6` data
+    prefix: Vec<u8>,
+    /// Symbol table with the names to be patched
+    symbols: Vec<u8>,
+    /// Offsets in `symbols` of the head, code and `__imp_` symbols
+    name_offsets: Vec<usize>,
+    /// Offsets of the `.idata$6` size in `prefix` and of its section
+    /// definition in `symbols`, for named imports
+    hint_name_offsets: Option<[usize; 2]>,
+    /// Offsets of the `.idata$5` and `.idata$4` ordinals of ordinal imports
+    ordinal_offsets: Option<[usize; 2]>,
+}
+
+impl<'a> MemberTemplates<'a> {
+    fn new(factory: &'a ObjectFactory<'a>) -> Result<Self, Error> {
+        // Symbols of the i386 head are mangled, see `make_one`.
+        let prefix = if factory.machine == MachineType::I386 {
+            "_"
+        } else {
+            ""
+        };
+        let head_name = format!("{}_head_{}", prefix, factory.symbol_stem).into_bytes();
+        let template = |data, no_name| {
+            let export = ShortExport {
+                name: "template".to_string(),
+                data,
+                no_name,
+                ..Default::default()
             };
-            let len = 2 + export_name.len() + 1;
-            let mut id6_data = vec![0; len];
-            let ord = export.ordinal;
-            id6_data[0] = ord as u8;
-            id6_data[1] = (ord >> 8) as u8;
-            id6_data[2..len - 1].copy_from_slice(export_name.as_bytes());
-            obj.append_section_data(id6, &id6_data, 2);
+            let member = factory.make_one(&export, 0)?;
+            MemberTemplate::new(&member.data, &export, &head_name)
+        };
+        Ok(Self {
+            factory,
+            templates: [
+                template(false, false)?,
+                template(false, true)?,
+                template(true, false)?,
+                template(true, true)?,
+            ],
+            head_name,
+        })
+    }
+
+    /// Generate the same member as [`ObjectFactory::make_one`]
+    fn make_one(&self, export: &ShortExport, seq: usize) -> Result<ArchiveMember, Error> {
+        check_export_name(export)?;
+        let template = &self.templates[export.data as usize * 2 + export.no_name as usize];
+        let mut data = template.prefix.clone();
+        let mut symbols = template.symbols.clone();
+        if let Some(offsets) = template.ordinal_offsets {
+            for offset in offsets {
+                data[offset..offset + 2].copy_from_slice(&export.ordinal.to_le_bytes());
+            }
+        }
+        if let Some([size, definition]) = template.hint_name_offsets {
+            let hint_name = self.factory.hint_name(export);
+            set_u32(&mut data, size, hint_name.len());
+            set_u32(
+                &mut symbols,
+                definition + memoffset::offset_of!(ImageAuxSymbolSection, length),
+                hint_name.len(),
+            );
+            set_u32(
+                &mut symbols,
+                definition + memoffset::offset_of!(ImageAuxSymbolSection, check_sum),
+                checksum(&hint_name) as usize,
+            );
+            data.extend_from_slice(&hint_name);
         }
+        let symtab = data.len();
+        set_u32(
+            &mut data,
+            memoffset::offset_of!(ImageFileHeader, pointer_to_symbol_table),
+            symtab,
+        );
 
-        let name = format!("{}_s{:05}.o", self.output_name.replace('.', "_"), self.seq);
-        self.seq += 1;
+        let imp_name = format!("__imp_{}", export.name).into_bytes();
+        let mut names = vec![&self.head_name[..]];
+        if !export.data {
+            names.push(export.name.as_bytes());
+        }
+        names.push(&imp_name);
+        let (strtab, offsets) = string_table(&names);
+        for ((name, offset), &field) in names.iter().zip(offsets).zip(&template.name_offsets) {
+            let field = &mut symbols[field..][..8];
+            match offset {
+                Some(offset) => {
+                    field[..4].fill(0);
+                    field[4..].copy_from_slice(&offset.to_le_bytes());
+                }
+                None => {
+                    field.fill(0);
+                    field[..name.len()].copy_from_slice(name);
+                }
+            }
+        }
+        data.extend_from_slice(&symbols);
+        data.extend_from_slice(&strtab);
 
         Ok(ArchiveMember {
-            name,
-            data: obj.write().map_err(|e| Error::other(e.to_string()))?,
+            name: self.factory.member_name(seq),
+            data,
+        })
+    }
+}
+
+impl MemberTemplate {
+    /// Locate the fields to patch in the member generated for `export`
+    fn new(member: &[u8], export: &ShortExport, head_name: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::encoding("invalid import member template");
+        let file = CoffFile::<&[u8]>::parse(member)?;
+        let header = file.coff_header();
+        let symtab = header.pointer_to_symbol_table.get(LE) as usize;
+        let symtab_end = symtab + header.number_of_symbols.get(LE) as usize * IMAGE_SIZEOF_SYMBOL;
+        let section_table =
+            mem::size_of::<ImageFileHeader>() + header.size_of_optional_header.get(LE) as usize;
+        let section_data = |name: &str| {
+            let section = file.section_by_name(name).ok_or_else(invalid)?;
+            let (offset, size) = section.file_range().ok_or_else(invalid)?;
+            Ok::<_, Error>((section.index().0, offset as usize, size as usize))
+        };
+
+        let (prefix_end, hint_name_offsets, ordinal_offsets) = if export.no_name {
+            let (_, id5, _) = section_data(".idata$5")?;
+            let (_, id4, _) = section_data(".idata$4")?;
+            (symtab, None, Some([id5, id4]))
+        } else {
+            let (index, offset, size) = section_data(".idata$6")?;
+            // The hint and name are appended, nothing may follow them.
+            if offset + size != symtab {
+                return Err(invalid());
+            }
+            let size_field = section_table
+                + (index - 1) * mem::size_of::<ImageSectionHeader>()
+                + memoffset::offset_of!(ImageSectionHeader, size_of_raw_data);
+            // The section symbol is followed by its definition.
+            let symbol = file
+                .symbols()
+                .find(|symbol| {
+                    symbol.kind() == SymbolKind::Section && symbol.name() == Ok(".idata$6")
+                })
+                .ok_or_else(invalid)?;
+            let definition = (symbol.index().0 + 1) * IMAGE_SIZEOF_SYMBOL;
+            (offset, Some([size_field, definition]), None)
+        };
+
+        let imp_name = format!("__imp_{}", export.name);
+        let mut names = vec![head_name];
+        if !export.data {
+            names.push(export.name.as_bytes());
+        }
+        names.push(imp_name.as_bytes());
+        let name_offsets = names
+            .iter()
+            .map(|name| {
+                let symbol = file
+                    .symbols()
+                    .find(|symbol| symbol.name_bytes() == Ok(*name))
+                    .ok_or_else(invalid)?;
+                Ok(symbol.index().0 * IMAGE_SIZEOF_SYMBOL)
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            prefix: member.get(..prefix_end).ok_or_else(invalid)?.to_vec(),
+            symbols: member.get(symtab..symtab_end).ok_or_else(invalid)?.to_vec(),
+            name_offsets,
+            hint_name_offsets,
+            ordinal_offsets,
         })
     }
 }
 
+fn set_u32(data: &mut [u8], offset: usize, value: usize) {
+    data[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+}
+
+/// Lay out a COFF string table like `object::write` does: sorted by
+/// reversed name, sharing the bytes of names ending the previous one
+///
+/// Returns the table and the offsets of the names that don't fit in a
+/// symbol.
+fn string_table(names: &[&[u8]]) -> (Vec<u8>, Vec<Option<u32>>) {
+    let mut long: Vec<&[u8]> = names.iter().copied().filter(|n| n.len() > 8).collect();
+    long.sort_by(|a, b| b.iter().rev().cmp(a.iter().rev()));
+    long.dedup();
+    let mut data = vec![0; 4];
+    let mut offsets = Vec::with_capacity(long.len());
+    let mut previous: &[u8] = &[];
+    for name in &long {
+        if previous.ends_with(name) {
+            offsets.push(data.len() - name.len() - 1);
+        } else {
+            offsets.push(data.len());
+            data.extend_from_slice(name);
+            data.push(0);
+            previous = name;
+        }
+    }
+    let len = data.len();
+    set_u32(&mut data, 0, len);
+    let offsets = names
+        .iter()
+        .map(|name| {
+            let index = long.iter().position(|long| long == name)?;
+            Some(offsets[index] as u32)
+        })
+        .collect();
+    (data, offsets)
+}
+
+/// JamCRC of the section data, as in COFF section definitions
+fn checksum(data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new_with_initial(0xffff_ffff);
+    hasher.update(data);
+    !hasher.finalize()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -506,10 +1050,107 @@ mod test {
             MachineType::AMD64,
         );
         import_lib
-            .write_to(&mut Cursor::new(Vec::new()))
+            .write_to(&mut Cursor::new(Vec::new()), &WriteOptions::default())
             .unwrap_err();
     }
 
+    #[test]
+    fn test_export_members_order() {
+        let text: String = (0..300).map(|i| format!("func{}\n", i)).collect();
+        let def = ModuleDef::parse(&format!("EXPORTS\n{}", text), MachineType::AMD64).unwrap();
+        let import_lib = GnuImportLibrary::new(def, MachineType::AMD64);
+        let factory = ObjectFactory::new("foo.dll", MachineType::AMD64).unwrap();
+        let members = import_lib
+            .make_exports(&MemberTemplates::new(&factory).unwrap())
+            .unwrap();
+        assert_eq!(members.len(), 300);
+        for (seq, (member, export)) in members.iter().zip(&import_lib.def.exports).enumerate() {
+            let expected = factory.make_one(export, seq).unwrap();
+            assert_eq!(member.name, format!("foo_dll_a_s{:05}.o", seq));
+            assert_eq!(member.name, expected.name);
+            assert_eq!(member.data, expected.data);
+        }
+    }
+
+    #[test]
+    fn test_member_templates() {
+        let machines = [
+            MachineType::I386,
+            MachineType::AMD64,
+            MachineType::ARMNT,
+            MachineType::ARM64,
+            MachineType::ARM64EC,
+        ];
+        // Short names are stored in the symbols, long names share the bytes
+        // of other names ending with them.
+        let names = [
+            "a",
+            "_a",
+            "abcdefgh",
+            "abcdefghi",
+            "foo_dll",
+            "_head_foo_dll",
+            "x_head_foo_dll",
+            "imp_a",
+            "__imp_a",
+            "PyObject_GenericGetAttr",
+            "@fastcall@8",
+            "?cpp@@YAXXZ",
+        ];
+        for machine in machines {
+            let stems = [None, None, None, Some("a_head_foo_dll")];
+            for (import_name, stem) in ["a", "foo.dll", "python39.dll", "foo.dll"]
+                .into_iter()
+                .zip(stems)
+            {
+                let options = WriteOptions {
+                    gnu_symbol_stem: stem.map(str::to_string),
+                    ..Default::default()
+                };
+                let factory = ObjectFactory::new(import_name, machine)
+                    .unwrap()
+                    .with_options(&options)
+                    .unwrap();
+                let templates = MemberTemplates::new(&factory).unwrap();
+                for (seq, name) in names.iter().enumerate() {
+                    for flags in 0..8 {
+                        let export = ShortExport {
+                            name: name.to_string(),
+                            export_as: (flags & 4 != 0).then(|| "exported".to_string()),
+                            ordinal: 0x1234 + seq as u16,
+                            no_name: flags & 2 != 0,
+                            data: flags & 1 != 0,
+                            ..Default::default()
+                        };
+                        let expected = factory.make_one(&export, seq).unwrap();
+                        let member = templates.make_one(&export, seq).unwrap();
+                        assert_eq!(member.name, expected.name);
+                        assert!(
+                            member.data == expected.data,
+                            "{:?} {} {:?}",
+                            machine,
+                            import_name,
+                            export
+                        );
+                    }
+                }
+                let export = ShortExport::default();
+                assert!(templates.make_one(&export, 0).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_string_table() {
+        let (data, offsets) = string_table(&[b"_head_foo_dll", b"foo_dll", b"__imp_foo_dll"]);
+        assert_eq!(&data[4..], b"__imp_foo_dll\0_head_foo_dll\0");
+        assert_eq!(&data[..4], &32u32.to_le_bytes());
+        assert_eq!(offsets, [Some(18), None, Some(4)]);
+        let (data, offsets) = string_table(&[b"a", b"foo_dll_long", b"dll_long"]);
+        assert_eq!(&data[4..], b"foo_dll_long\0");
+        assert_eq!(offsets, [None, Some(4), None]);
+    }
+
     /// Regression test for the i386 jump-stub relocation. The `ff 25 disp32`
     /// instruction is an absolute indirect jump on i386, so the disp32 must
     /// be patched with the absolute VA of the IAT entry. This requires
@@ -518,19 +1159,21 @@ mod test {
     /// and the relocation must point at the `__imp__<name>` symbol.
     #[test]
     fn test_i386_jump_stub_relocation() {
-        let mut factory = ObjectFactory::new("foo.dll", MachineType::I386).unwrap();
+        let factory = ObjectFactory::new("foo.dll", MachineType::I386).unwrap();
         let export = ShortExport {
             name: "_bar".to_string(), // i386 names are mangled with a leading underscore
             ext_name: None,
             symbol_name: String::new(),
             alias_target: String::new(),
+            export_as: None,
             ordinal: 0,
             no_name: false,
             data: false,
             private: false,
             constant: false,
+            forward: None,
         };
-        let member = factory.make_one(&export).unwrap();
+        let member = factory.make_one(&export, 0).unwrap();
         let coff = &member.data[..];
 
         // --- Parse COFF file header (20 bytes) ---
@@ -614,7 +1257,7 @@ mod test {
     #[ignore]
     #[test]
     fn debug_head_tail_export() {
-        let mut factory = ObjectFactory::new("python39.dll", MachineType::AMD64).unwrap();
+        let factory = ObjectFactory::new("python39.dll", MachineType::AMD64).unwrap();
         let head = factory.make_head().unwrap();
         std::fs::write("head.o", head.data).unwrap();
 
@@ -626,13 +1269,15 @@ mod test {
             ext_name: None,
             symbol_name: "".to_string(),
             alias_target: "".to_string(),
+            export_as: None,
             ordinal: 0,
             no_name: false,
             data: false,
             private: false,
             constant: false,
+            forward: None,
         };
-        let exp = factory.make_one(&export).unwrap();
+        let exp = factory.make_one(&export, 0).unwrap();
         std::fs::write("exp.o", exp.data).unwrap();
     }
 }
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+
+use object::coff::CoffFile;
+use object::pe::*;
+use object::read::archive::ArchiveFile;
+use object::read::pe::{ExportTarget, ImageNtHeaders, ImageOptionalHeader, PeFile};
+use object::{FileKind, LittleEndian as LE, Object, ObjectSection, ObjectSymbol, SymbolKind};
+
+use crate::def::{is_decorated, Forward, ForwardTarget, ModuleDef, ShortExport};
+use crate::{Error, MachineType};
+
+/// Import type of a short import member
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportType {
+    /// Executable code, the member provides a thunk and an `__imp_` symbol
+    Code,
+    /// Data, the member only provides an `__imp_` symbol
+    Data,
+    /// Constant
+    Const,
+}
+
+/// How the name imported from the DLL is derived from the symbol name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportNameType {
+    /// Import by ordinal, the hint holds the ordinal
+    Ordinal,
+    /// Import by the symbol name as is
+    Name,
+    /// Import by the symbol name with its leading `?`, `@` or `_` removed
+    NoPrefix,
+    /// Import by the symbol name with its leading `?`, `@` or `_` removed
+    /// and everything after the first `@` truncated
+    Undecorate,
+    /// Import by a name stored explicitly in the member
+    ExportAs,
+}
+
+impl ImportNameType {
+    /// The name imported from the DLL for `symbol`, `None` for the types
+    /// that don't derive it from the symbol
+    pub(crate) fn apply(self, symbol: &str) -> Option<&str> {
+        match self {
+            Self::Name => Some(symbol),
+            Self::NoPrefix => Some(strip_prefix(symbol)),
+            Self::Undecorate => {
+                let name = strip_prefix(symbol);
+                Some(name.split('@').next().unwrap_or(name))
+            }
+            Self::Ordinal | Self::ExportAs => None,
+        }
+    }
+}
+
+/// A single archive member of an import library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberInfo {
+    /// Archive member name
+    pub name: String,
+    /// Name of the DLL the member refers to, if it could be determined
+    pub dll_name: Option<String>,
+    /// Machine type recorded in the member, `None` for unsupported machines
+    pub machine: Option<MachineType>,
+    /// Import type, `None` for members that don't describe a single import
+    pub import_type: Option<ImportType>,
+    /// Import name type, `None` for members that don't describe a single import
+    pub name_type: Option<ImportNameType>,
+    /// Ordinal for imports by ordinal, otherwise the hint
+    pub hint: u16,
+    /// Name imported from the DLL, `None` for imports by ordinal and
+    /// members that don't describe a single import
+    pub import_name: Option<String>,
+    /// Public symbols defined by the member, in the order the linker sees them
+    pub symbols: Vec<String>,
+}
+
+/// Contents of an MSVC or GNU flavored import library
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportLibraryInfo {
+    /// Archive members in archive order, without the archive symbol tables
+    pub members: Vec<MemberInfo>,
+}
+
+impl ImportLibraryInfo {
+    /// Parse an import library archive
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let archive = ArchiveFile::parse(data)?;
+        let mut members = Vec::new();
+        // GNU import libraries only record the DLL name in the tail member,
+        // export members reach it through `_head_*` -> `*_iname`.
+        let mut heads = HashMap::new();
+        let mut inames = HashMap::new();
+        for member in archive.members() {
+            let member = member?;
+            // Skip special members such as `/<ECSYMBOLS>/`.
+            if member.name().starts_with(b"/<") {
+                continue;
+            }
+            let name = String::from_utf8_lossy(member.name()).into_owned();
+            let member_data = member.data(data)?;
+            let info = if is_short_import(member_data) {
+                parse_short_import(name, member_data)?
+            } else {
+                parse_object(name, member_data, &mut heads, &mut inames)?
+            };
+            members.push(info);
+        }
+
+        for info in &mut members {
+            if let Some(head) = info.head.as_ref() {
+                info.member.dll_name = heads.get(head).and_then(|iname| inames.get(iname)).cloned();
+            }
+        }
+        Ok(Self {
+            members: members.into_iter().map(|info| info.member).collect(),
+        })
+    }
+
+    /// Reconstruct a module definition from the import members, e.g. to
+    /// [`diff`](ModuleDef::diff) a library read back from disk
+    ///
+    /// Export names are the symbol names without the `__imp_` prefix and
+    /// ordinals are taken from the hints, which is where the writers of
+    /// this crate put them.
+    pub fn to_module_def(&self) -> ModuleDef {
+        let mut def = ModuleDef::default();
+        for member in &self.members {
+            let (Some(import_type), Some(name_type)) = (member.import_type, member.name_type)
+            else {
+                continue;
+            };
+            let Some(name) = member
+                .symbols
+                .iter()
+                .find_map(|symbol| symbol.strip_prefix("__imp_"))
+            else {
+                continue;
+            };
+            if def.import_name.is_empty() {
+                def.import_name = member.dll_name.clone().unwrap_or_default();
+            }
+            def.exports.push(ShortExport {
+                name: name.to_string(),
+                ordinal: member.hint,
+                no_name: name_type == ImportNameType::Ordinal,
+                data: import_type == ImportType::Data,
+                constant: import_type == ImportType::Const,
+                ..Default::default()
+            });
+        }
+        def
+    }
+}
+
+impl ModuleDef {
+    /// Read the export table of a DLL into a module definition
+    ///
+    /// Exports outside of executable sections are marked `DATA` and exports
+    /// without a name are called `ord_<ordinal>`. Names are mangled the way
+    /// [`ModuleDef::parse`] mangles them for the DLL's machine type.
+    pub fn from_dll(data: &[u8]) -> Result<ModuleDef, Error> {
+        match FileKind::parse(data)? {
+            FileKind::Pe32 => read_dll::<ImageNtHeaders32>(data),
+            FileKind::Pe64 => read_dll::<ImageNtHeaders64>(data),
+            _ => Err(Error::encoding("not a PE image")),
+        }
+    }
+}
+
+fn read_dll<Pe: ImageNtHeaders>(data: &[u8]) -> Result<ModuleDef, Error> {
+    let file = PeFile::<Pe>::parse(data)?;
+    let nt_headers = file.nt_headers();
+    let is_i386 = nt_headers.file_header().machine.get(LE) == IMAGE_FILE_MACHINE_I386;
+    let header = nt_headers.optional_header();
+    let mut def = ModuleDef {
+        image_base: header.image_base(),
+        stack_reserve: header.size_of_stack_reserve(),
+        stack_commit: header.size_of_stack_commit(),
+        heap_reserve: header.size_of_heap_reserve(),
+        heap_commit: header.size_of_heap_commit(),
+        major_image_version: header.major_image_version().into(),
+        minor_image_version: header.minor_image_version().into(),
+        major_os_version: header.major_operating_system_version().into(),
+        minor_os_version: header.minor_operating_system_version().into(),
+        ..Default::default()
+    };
+    let Some(table) = file.export_table()? else {
+        return Ok(def);
+    };
+    let name = table.name_from_pointer(table.directory().name.get(LE))?;
+    def.import_name = String::from_utf8_lossy(name).into_owned();
+
+    let mangle = |name: String| {
+        if is_i386 && !is_decorated(&name) {
+            format!("_{}", name)
+        } else {
+            name
+        }
+    };
+    for export in table.exports()? {
+        let ordinal =
+            u16::try_from(export.ordinal).map_err(|_| Error::encoding("ordinal out of range"))?;
+        let name = match export.name {
+            Some(name) => String::from_utf8_lossy(name).into_owned(),
+            None => format!("ord_{}", ordinal),
+        };
+        let mut short_export = ShortExport {
+            ordinal,
+            no_name: export.name.is_none(),
+            ..Default::default()
+        };
+        let forward = match export.target {
+            ExportTarget::Address(0) => continue,
+            ExportTarget::Address(address) => {
+                let executable = file
+                    .section_table()
+                    .section_containing(address)
+                    .is_some_and(|section| {
+                        section.characteristics.get(LE) & IMAGE_SCN_MEM_EXECUTE != 0
+                    });
+                short_export.data = !executable;
+                None
+            }
+            ExportTarget::ForwardByName(dll, target) => Some(Forward {
+                dll: String::from_utf8_lossy(dll).into_owned(),
+                target: ForwardTarget::Name(String::from_utf8_lossy(target).into_owned()),
+            }),
+            ExportTarget::ForwardByOrdinal(dll, target) => Some(Forward {
+                dll: String::from_utf8_lossy(dll).into_owned(),
+                target: ForwardTarget::Ordinal(
+                    u16::try_from(target).map_err(|_| Error::encoding("ordinal out of range"))?,
+                ),
+            }),
+        };
+        // Same shape as "name = dll.target" from the def parser
+        match forward {
+            Some(forward) => {
+                short_export.name = forward.to_string();
+                short_export.ext_name = Some(mangle(name));
+                short_export.forward = Some(forward);
+            }
+            None => short_export.name = mangle(name),
+        }
+        def.exports.push(short_export);
+    }
+    Ok(def)
+}
+
+#[derive(Debug)]
+struct PendingMember {
+    member: MemberInfo,
+    /// Head symbol that leads to the DLL name of a GNU member
+    head: Option<String>,
+}
+
+fn is_short_import(data: &[u8]) -> bool {
+    data.len() >= 4 && data[0..2] == [0, 0] && data[2..4] == [0xff, 0xff]
+}
+
+impl MachineType {
+    pub(crate) fn from_u16(machine: u16) -> Option<Self> {
+        match machine {
+            IMAGE_FILE_MACHINE_I386 => Some(Self::I386),
+            IMAGE_FILE_MACHINE_ARMNT => Some(Self::ARMNT),
+            IMAGE_FILE_MACHINE_AMD64 => Some(Self::AMD64),
+            IMAGE_FILE_MACHINE_ARM64 => Some(Self::ARM64),
+            IMAGE_FILE_MACHINE_ARM64EC => Some(Self::ARM64EC),
+            IMAGE_FILE_MACHINE_ARM64X => Some(Self::ARM64X),
+            _ => None,
+        }
+    }
+}
+
+fn parse_short_import(name: String, data: &[u8]) -> Result<PendingMember, Error> {
+    let mut offset = 0;
+    let header = ImportObjectHeader::parse(data, &mut offset)?;
+    let import = header.parse_data(data, &mut offset)?;
+    let machine = header.machine.get(LE);
+    let symbol = String::from_utf8_lossy(import.symbol()).into_owned();
+
+    let import_type = match header.import_type() {
+        IMPORT_OBJECT_CODE => ImportType::Code,
+        IMPORT_OBJECT_DATA => ImportType::Data,
+        IMPORT_OBJECT_CONST => ImportType::Const,
+        other => return Err(Error::encoding(format!("unknown import type: {}", other))),
+    };
+    let name_type = match header.name_type() {
+        IMPORT_OBJECT_ORDINAL => ImportNameType::Ordinal,
+        IMPORT_OBJECT_NAME => ImportNameType::Name,
+        IMPORT_OBJECT_NAME_NO_PREFIX => ImportNameType::NoPrefix,
+        IMPORT_OBJECT_NAME_UNDECORATE => ImportNameType::Undecorate,
+        IMPORT_OBJECT_NAME_EXPORTAS => ImportNameType::ExportAs,
+        other => {
+            return Err(Error::encoding(format!(
+                "unknown import name type: {}",
+                other
+            )))
+        }
+    };
+    let import_name = match name_type {
+        ImportNameType::ExportAs => import
+            .export()
+            .map(|export| String::from_utf8_lossy(export).into_owned()),
+        _ => name_type.apply(&symbol).map(str::to_string),
+    };
+
+    // Mirrors the symbols the archive writer puts into the symbol table.
+    let demangled = if machine == IMAGE_FILE_MACHINE_ARM64EC {
+        demangle_arm64ec(&symbol)
+    } else {
+        symbol.clone()
+    };
+    let mut symbols = vec![format!("__imp_{}", demangled)];
+    if import_type == ImportType::Code {
+        symbols.push(demangled.clone());
+        if machine == IMAGE_FILE_MACHINE_ARM64EC {
+            symbols.push(format!("__imp_aux_{}", demangled));
+            symbols.push(symbol);
+        }
+    }
+
+    Ok(PendingMember {
+        member: MemberInfo {
+            name,
+            dll_name: Some(String::from_utf8_lossy(import.dll()).into_owned()),
+            machine: MachineType::from_u16(machine),
+            import_type: Some(import_type),
+            name_type: Some(name_type),
+            hint: header.ordinal_or_hint.get(LE),
+            import_name,
+            symbols,
+        },
+        head: None,
+    })
+}
+
+fn strip_prefix(name: &str) -> &str {
+    name.strip_prefix(['?', '@', '_']).unwrap_or(name)
+}
+
+pub(crate) fn demangle_arm64ec(name: &str) -> String {
+    if let Some(name) = name.strip_prefix('#') {
+        name.to_string()
+    } else if name.starts_with('?') {
+        name.replacen("$$h", "", 1)
+    } else {
+        name.to_string()
+    }
+}
+
+fn parse_object(
+    name: String,
+    data: &[u8],
+    heads: &mut HashMap<String, String>,
+    inames: &mut HashMap<String, String>,
+) -> Result<PendingMember, Error> {
+    let file = CoffFile::<&[u8]>::parse(data)?;
+    let machine = MachineType::from_u16(file.coff_header().machine.get(LE));
+    let section_data = |name: &str| -> Result<Option<&[u8]>, Error> {
+        match file.section_by_name(name) {
+            Some(section) => section.data().map(Some).map_err(Error::from),
+            None => Ok(None),
+        }
+    };
+
+    let mut member = MemberInfo {
+        name,
+        dll_name: None,
+        machine,
+        import_type: None,
+        name_type: None,
+        hint: 0,
+        import_name: None,
+        symbols: Vec::new(),
+    };
+    let mut head_ref = None;
+    let mut head_def = None;
+    let mut iname_ref = None;
+    let mut iname_def = None;
+    let mut has_thunk = false;
+    for symbol in file.symbols() {
+        if matches!(symbol.kind(), SymbolKind::File | SymbolKind::Section) || !symbol.is_global() {
+            continue;
+        }
+        let symbol_name = symbol.name()?;
+        if symbol.is_undefined() {
+            if is_head_symbol(symbol_name) {
+                head_ref = Some(symbol_name.to_string());
+            } else if symbol_name.ends_with("_iname") {
+                iname_ref = Some(symbol_name.to_string());
+            }
+            continue;
+        }
+        if is_head_symbol(symbol_name) {
+            head_def = Some(symbol_name.to_string());
+        } else if symbol_name.ends_with("_iname") {
+            iname_def = Some(symbol_name.to_string());
+        } else if !symbol_name.starts_with("__imp_") {
+            has_thunk = true;
+        }
+        member.symbols.push(symbol_name.to_string());
+    }
+
+    let mut head_lookup = None;
+    if member
+        .symbols
+        .iter()
+        .any(|sym| sym.starts_with("__IMPORT_DESCRIPTOR_"))
+    {
+        // MSVC import descriptor, `.idata$6` holds the DLL name.
+        member.dll_name = section_data(".idata$6")?.map(c_str);
+    } else if let (Some(head), Some(iname)) = (head_def, iname_ref) {
+        // GNU head member
+        heads.insert(head.clone(), iname);
+        head_lookup = Some(head);
+    } else if let Some(iname) = iname_def {
+        // GNU tail member, `.idata$7` holds the DLL name.
+        let dll_name = section_data(".idata$7")?.map(c_str);
+        if let Some(dll_name) = dll_name.as_ref() {
+            inames.insert(iname, dll_name.clone());
+        }
+        member.dll_name = dll_name;
+    } else if let Some(head) = head_ref {
+        // GNU export member
+        if let Some(thunk) = section_data(".idata$5")? {
+            let ordinal_flag = match thunk.len() {
+                4..=7 => u32::from_le_bytes([thunk[0], thunk[1], thunk[2], thunk[3]]) >> 31 == 1,
+                8.. => thunk[7] & 0x80 != 0,
+                _ => false,
+            };
+            if ordinal_flag {
+                member.name_type = Some(ImportNameType::Ordinal);
+                member.hint = u16::from_le_bytes([thunk[0], thunk[1]]);
+            } else {
+                let hint_name = section_data(".idata$6")?.unwrap_or_default();
+                if hint_name.len() >= 2 {
+                    member.hint = u16::from_le_bytes([hint_name[0], hint_name[1]]);
+                    member.import_name = Some(c_str(&hint_name[2..]));
+                }
+                member.name_type = Some(ImportNameType::Name);
+            }
+            member.import_type = Some(if has_thunk {
+                ImportType::Code
+            } else {
+                ImportType::Data
+            });
+        }
+        head_lookup = Some(head);
+    }
+    Ok(PendingMember {
+        member,
+        head: head_lookup,
+    })
+}
+
+/// `_head_*`, with an extra leading underscore on i386
+fn is_head_symbol(name: &str) -> bool {
+    name.trim_start_matches('_').starts_with("head_")
+}
+
+fn c_str(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
@@ -1,23 +1,55 @@
-#[cfg(any(not(feature = "msvc"), not(feature = "gnu")))]
-use std::io::ErrorKind;
-use std::io::{Error, Seek, Write};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Cursor, Seek, Write};
+use std::str::FromStr;
 
 use object::pe::*;
 
+/// Cargo build script helper
+#[cfg(feature = "build")]
+pub mod build;
 /// Parse .DEF file
 pub mod def;
+/// Export directory layout shared by stub DLLs and export objects
+#[cfg(any(feature = "stub", feature = "exp"))]
+mod edata;
+/// Error type
+mod error;
+/// Export object generator
+#[cfg(feature = "exp")]
+mod exp;
 /// GNU binutils flavored import library
 #[cfg(feature = "gnu")]
 mod gnu;
+/// Inspect existing import libraries
+pub mod inspect;
+/// Resource limits for untrusted inputs
+mod limits;
 /// MSVC flavored import library
 #[cfg(feature = "msvc")]
 mod msvc;
+/// Archive layout options
+mod options;
+/// Stub DLL generator
+#[cfg(feature = "stub")]
+mod stub;
 
+pub use self::error::Error;
+#[cfg(feature = "exp")]
+pub use self::exp::ExportObject;
+#[cfg(feature = "gnu")]
+#[doc(hidden)]
+pub use self::gnu::bench_export_members;
 #[cfg(feature = "gnu")]
 use self::gnu::GnuImportLibrary;
+use self::limits::LimitedWriter;
+pub use self::limits::{Limit, Limits};
 #[cfg(feature = "msvc")]
 use self::msvc::MsvcImportLibrary;
-use crate::def::ModuleDef;
+pub use self::options::{GnuMemberNames, SymbolOrder, WriteOptions};
+#[cfg(feature = "stub")]
+pub use self::stub::StubDll;
+use crate::def::{ModuleDef, Transform, TransformReport};
 
 /// Machine types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,6 +77,80 @@ impl MachineType {
             Self::I386 => IMAGE_REL_I386_DIR32NB,
         }
     }
+
+    #[cfg(any(feature = "gnu", feature = "exp"))]
+    fn to_arch(self) -> object::Architecture {
+        use object::Architecture::*;
+        match self {
+            Self::AMD64 => X86_64,
+            Self::ARMNT => Arm,
+            Self::ARM64 | Self::ARM64EC | Self::ARM64X => Aarch64,
+            Self::I386 => I386,
+        }
+    }
+}
+
+/// Parses llvm-dlltool's `-m` machine names
+impl FromStr for MachineType {
+    type Err = Error;
+
+    fn from_str(machine: &str) -> Result<Self, Error> {
+        match machine {
+            "i386" => Ok(Self::I386),
+            "i386:x86-64" => Ok(Self::AMD64),
+            "arm" => Ok(Self::ARMNT),
+            "arm64" => Ok(Self::ARM64),
+            "arm64ec" => Ok(Self::ARM64EC),
+            "arm64x" => Ok(Self::ARM64X),
+            _ => Err(Error::validation(format!("unknown machine: {}", machine))),
+        }
+    }
+}
+
+/// Formats as llvm-dlltool's `-m` machine name
+impl fmt::Display for MachineType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::I386 => "i386",
+            Self::AMD64 => "i386:x86-64",
+            Self::ARMNT => "arm",
+            Self::ARM64 => "arm64",
+            Self::ARM64EC => "arm64ec",
+            Self::ARM64X => "arm64x",
+        })
+    }
+}
+
+impl MachineType {
+    /// Machine type of a Windows target triple such as
+    /// `x86_64-pc-windows-msvc` or `i686-w64-mingw32`
+    pub fn from_target_triple(triple: &str) -> Result<Self, Error> {
+        let (arch, _) = split_target_triple(triple)?;
+        match arch {
+            "i386" | "i486" | "i586" | "i686" => Ok(Self::I386),
+            "x86_64" => Ok(Self::AMD64),
+            "thumbv7a" | "armv7" => Ok(Self::ARMNT),
+            "aarch64" | "arm64" => Ok(Self::ARM64),
+            "arm64ec" => Ok(Self::ARM64EC),
+            _ => Err(Error::Unsupported(format!(
+                "unsupported architecture {} in target triple: {}",
+                arch, triple
+            ))),
+        }
+    }
+}
+
+/// Split a Windows target triple into its architecture and environment
+fn split_target_triple(triple: &str) -> Result<(&str, &str), Error> {
+    let parts: Vec<&str> = triple.split('-').collect();
+    match parts[..] {
+        [arch, _, "windows", env] => Ok((arch, env)),
+        [arch, _, "mingw32"] => Ok((arch, "gnu")),
+        _ => Err(Error::validation(format!(
+            "not a Windows target triple: {}",
+            triple
+        ))),
+    }
 }
 
 #[derive(Debug)]
@@ -53,8 +159,60 @@ struct ArchiveMember {
     data: Vec<u8>,
 }
 
+/// Writer telling the I/O errors of `inner` apart from the errors
+/// `ar_archive_writer` raises about the archive contents, which it reports
+/// as `io::Error` too
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+struct ArchiveWriter<'a, W> {
+    inner: &'a mut W,
+    failed: bool,
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+impl<'a, W: Write + Seek> ArchiveWriter<'a, W> {
+    fn new(inner: &'a mut W) -> Self {
+        Self {
+            inner,
+            failed: false,
+        }
+    }
+
+    /// Turn the errors that didn't come from `inner` into
+    /// [`Error::Validation`]
+    fn finish(&self, result: std::io::Result<()>) -> Result<(), Error> {
+        match result {
+            Err(err) if !self.failed => Err(Error::validation(err.to_string())),
+            result => Ok(result?),
+        }
+    }
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+impl<W: Write> Write for ArchiveWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.failed |= result.is_err();
+        result
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let result = self.inner.flush();
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+impl<W: Seek> Seek for ArchiveWriter<'_, W> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let result = self.inner.seek(pos);
+        self.failed |= result.is_err();
+        result
+    }
+}
+
 /// Import library flavor
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Flavor {
     /// MSVC short import library
     Msvc,
@@ -62,6 +220,22 @@ pub enum Flavor {
     Gnu,
 }
 
+impl Flavor {
+    /// Import library flavor of a Windows target triple, GNU for the
+    /// `gnu` and `gnullvm` environments
+    pub fn from_target_triple(triple: &str) -> Result<Self, Error> {
+        let (_, env) = split_target_triple(triple)?;
+        match env {
+            "msvc" => Ok(Self::Msvc),
+            "gnu" | "gnullvm" => Ok(Self::Gnu),
+            _ => Err(Error::Unsupported(format!(
+                "unsupported environment {} in target triple: {}",
+                env, triple
+            ))),
+        }
+    }
+}
+
 /// Windows import library generator
 #[derive(Debug, Clone)]
 pub struct ImportLibrary {
@@ -69,6 +243,8 @@ pub struct ImportLibrary {
     native_def: Option<ModuleDef>,
     machine: MachineType,
     flavor: Flavor,
+    limits: Limits,
+    options: WriteOptions,
 }
 
 impl ImportLibrary {
@@ -107,20 +283,9 @@ impl ImportLibrary {
         machine: MachineType,
         flavor: Flavor,
     ) -> Self {
-        // If ext_name is set (if the "ext_name = name" syntax was used), overwrite
-        // name with ext_name and clear ext_name. When only creating an import
-        // library and not linking, the internal name is irrelevant.
-        for export in &mut def.exports {
-            if let Some(ext_name) = export.ext_name.take() {
-                export.name = ext_name;
-            }
-        }
+        take_ext_names(&mut def);
         let native_def = native_def.map(|mut nd| {
-            for export in &mut nd.exports {
-                if let Some(ext_name) = export.ext_name.take() {
-                    export.name = ext_name;
-                }
-            }
+            take_ext_names(&mut nd);
             nd
         });
         // Skipped i386 handling
@@ -130,33 +295,274 @@ impl ImportLibrary {
             native_def,
             machine,
             flavor,
+            limits: Limits::default(),
+            options: WriteOptions::default(),
         }
     }
 
+    /// Enforce `limits` when writing, see [`Limits`]
+    ///
+    /// The .def size limit only applies to parsing, see
+    /// [`ModuleDef::parse_with_limits`].
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Lay out the archive according to `options`, see [`WriteOptions`]
+    pub fn with_options(mut self, options: WriteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Get import library name
     pub fn import_name(&self) -> &str {
         &self.def.import_name
     }
 
+    /// Filter and rename the exports before writing, see [`Transform`]
+    ///
+    /// For ARM64X the transform applies to the native exports as well.
+    pub fn transform(&mut self, transform: &Transform) -> TransformReport {
+        let mut report = self.def.transform(transform, self.machine, self.flavor);
+        if let Some(native_def) = self.native_def.as_mut() {
+            report.extend(native_def.transform(transform, MachineType::ARM64, self.flavor));
+        }
+        report
+    }
+
+    /// Rewrite an existing MSVC or GNU import library to import from
+    /// `dll_name`, keeping its symbols, ordinals and import names
+    ///
+    /// The members are written in the order of `data`, with the headers
+    /// and GNU naming of `options`. GNU per-export members are renamed
+    /// from the member prefix of `data` to the new one.
+    pub fn retarget<W: Write + Seek>(
+        data: &[u8],
+        dll_name: &str,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> Result<(), Error> {
+        if dll_name.is_empty() || dll_name.contains('\0') {
+            return Err(Error::validation(format!(
+                "invalid DLL name: {:?}",
+                dll_name
+            )));
+        }
+        let info = inspect::ImportLibraryInfo::parse(data)?;
+        let has_symbol = |f: &dyn Fn(&str) -> bool| {
+            info.members
+                .iter()
+                .any(|member| member.symbols.iter().any(|symbol| f(symbol)))
+        };
+        let flavor = if has_symbol(&|symbol| symbol.starts_with("__IMPORT_DESCRIPTOR_")) {
+            Flavor::Msvc
+        } else if has_symbol(&|symbol| {
+            symbol.starts_with("_head_") || symbol.starts_with("__head_")
+        }) {
+            Flavor::Gnu
+        } else {
+            return Err(Error::encoding("not an import library"));
+        };
+        match flavor {
+            #[cfg(feature = "msvc")]
+            Flavor::Msvc => msvc::retarget_to(data, dll_name, writer, options),
+            #[cfg(not(feature = "msvc"))]
+            Flavor::Msvc => Err(Error::Unsupported(
+                "MSVC import library unsupported, enable 'msvc' feature to use it".to_string(),
+            )),
+            #[cfg(feature = "gnu")]
+            Flavor::Gnu => gnu::retarget_to(data, dll_name, writer, options),
+            #[cfg(not(feature = "gnu"))]
+            Flavor::Gnu => Err(Error::Unsupported(
+                "GNU import library unsupported, enable 'gnu' feature to use it".to_string(),
+            )),
+        }
+    }
+
     /// Write out the import library
-    pub fn write_to<W: Write + Seek>(self, writer: &mut W) -> Result<(), Error> {
+    pub fn write_to<W: Write + Seek>(&self, writer: &mut W) -> Result<(), Error> {
+        self.limits
+            .check_defs(std::iter::once(&self.def).chain(&self.native_def))?;
+        let mut writer = LimitedWriter::new(writer, self.limits.max_output_size)?;
+        let result = self.write_unlimited(&mut writer);
+        writer.finish(result)
+    }
+
+    /// Write out the import library to a new buffer
+    pub fn write_to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Cursor::new(Vec::new());
+        self.write_to(&mut buf)?;
+        Ok(buf.into_inner())
+    }
+
+    /// Write out the import library to a stream that can't seek, like stdout
+    /// or a pipe
+    ///
+    /// The archive is laid out in memory first, then written in one go.
+    pub fn write_unseekable<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&self.write_to_vec()?)?;
+        Ok(())
+    }
+
+    fn write_unlimited<W: Write + Seek>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut def = self.def.clone();
+        self.options.order_exports(&mut def);
+        let mut native_def = self.native_def.clone();
+        if let Some(native_def) = native_def.as_mut() {
+            self.options.order_exports(native_def);
+        }
         match self.flavor {
             #[cfg(feature = "msvc")]
-            Flavor::Msvc => {
-                MsvcImportLibrary::new(self.def, self.native_def, self.machine).write_to(writer)
+            Flavor::Msvc => MsvcImportLibrary::new(def, native_def, self.machine)
+                .write_to(writer, &self.options),
+            #[cfg(not(feature = "msvc"))]
+            Flavor::Msvc => Err(Error::Unsupported(
+                "MSVC import library unsupported, enable 'msvc' feature to use it".to_string(),
+            )),
+            #[cfg(feature = "gnu")]
+            Flavor::Gnu => GnuImportLibrary::new(def, self.machine).write_to(writer, &self.options),
+            #[cfg(not(feature = "gnu"))]
+            Flavor::Gnu => Err(Error::Unsupported(
+                "GNU import library unsupported, enable 'gnu' feature to use it".to_string(),
+            )),
+        }
+    }
+}
+
+/// Windows import library generator bundling the imports of several DLLs
+/// into one archive, like the Windows SDK umbrella libraries
+#[derive(Debug, Clone)]
+pub struct UmbrellaImportLibrary {
+    /// Module definition of each DLL along with its native ARM64X exports
+    defs: Vec<(ModuleDef, Option<ModuleDef>)>,
+    machine: MachineType,
+    flavor: Flavor,
+    limits: Limits,
+    options: WriteOptions,
+}
+
+impl UmbrellaImportLibrary {
+    /// Create new umbrella import library generator from `ModuleDef`s, one
+    /// per DLL
+    pub fn new(defs: Vec<ModuleDef>, machine: MachineType, flavor: Flavor) -> Self {
+        let defs = defs.into_iter().map(|def| (def, None)).collect();
+        Self::from_defs(defs, machine, flavor)
+    }
+
+    /// Create new ARM64X umbrella import library generator from the
+    /// ARM64EC/x64-compatible and the native ARM64 `ModuleDef` of each DLL,
+    /// see [`ImportLibrary::new_arm64x`]
+    pub fn new_arm64x(defs: Vec<(ModuleDef, ModuleDef)>, flavor: Flavor) -> Self {
+        let defs = defs
+            .into_iter()
+            .map(|(def, native_def)| (def, Some(native_def)))
+            .collect();
+        Self::from_defs(defs, MachineType::ARM64X, flavor)
+    }
+
+    fn from_defs(
+        mut defs: Vec<(ModuleDef, Option<ModuleDef>)>,
+        machine: MachineType,
+        flavor: Flavor,
+    ) -> Self {
+        for (def, native_def) in &mut defs {
+            take_ext_names(def);
+            if let Some(native_def) = native_def {
+                take_ext_names(native_def);
+            }
+        }
+        UmbrellaImportLibrary {
+            defs,
+            machine,
+            flavor,
+            limits: Limits::default(),
+            options: WriteOptions::default(),
+        }
+    }
+
+    /// Enforce `limits` when writing, see [`Limits`]
+    ///
+    /// The export limit applies to the exports of all DLLs together.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Lay out the archive according to `options`, see [`WriteOptions`]
+    ///
+    /// The GNU naming options name the members and symbols of a single DLL
+    /// and are rejected when writing.
+    pub fn with_options(mut self, options: WriteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Write out the import library
+    pub fn write_to<W: Write + Seek>(&self, writer: &mut W) -> Result<(), Error> {
+        // Members of MSVC import libraries are named after their DLL, so
+        // each DLL may only appear once.
+        let mut import_names = HashSet::new();
+        for (def, _) in &self.defs {
+            if !import_names.insert(def.import_name.to_ascii_lowercase()) {
+                return Err(Error::validation(format!(
+                    "duplicate import name: {}",
+                    def.import_name
+                )));
             }
+        }
+        self.limits.check_defs(
+            self.defs
+                .iter()
+                .flat_map(|(def, native_def)| std::iter::once(def).chain(native_def)),
+        )?;
+        let mut writer = LimitedWriter::new(writer, self.limits.max_output_size)?;
+        let result = self.write_unlimited(&mut writer);
+        writer.finish(result)
+    }
+
+    /// Write out the import library to a new buffer
+    pub fn write_to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Cursor::new(Vec::new());
+        self.write_to(&mut buf)?;
+        Ok(buf.into_inner())
+    }
+
+    fn write_unlimited<W: Write + Seek>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut defs = self.defs.clone();
+        for (def, native_def) in &mut defs {
+            self.options.order_exports(def);
+            if let Some(native_def) = native_def {
+                self.options.order_exports(native_def);
+            }
+        }
+        match self.flavor {
+            #[cfg(feature = "msvc")]
+            Flavor::Msvc => msvc::write_umbrella_to(defs, self.machine, writer, &self.options),
             #[cfg(not(feature = "msvc"))]
-            Flavor::Msvc => Err(Error::new(
-                ErrorKind::Unsupported,
-                "MSVC import library unsupported, enable 'msvc' feature to use it",
+            Flavor::Msvc => Err(Error::Unsupported(
+                "MSVC import library unsupported, enable 'msvc' feature to use it".to_string(),
             )),
             #[cfg(feature = "gnu")]
-            Flavor::Gnu => GnuImportLibrary::new(self.def, self.machine).write_to(writer),
+            Flavor::Gnu => {
+                let defs = defs.into_iter().map(|(def, _)| def).collect();
+                gnu::write_umbrella_to(defs, self.machine, writer, &self.options)
+            }
             #[cfg(not(feature = "gnu"))]
-            Flavor::Gnu => Err(Error::new(
-                ErrorKind::Unsupported,
-                "GNU import library unsupported, enable 'gnu' feature to use it",
+            Flavor::Gnu => Err(Error::Unsupported(
+                "GNU import library unsupported, enable 'gnu' feature to use it".to_string(),
             )),
         }
     }
 }
+
+/// If ext_name is set (if the "ext_name = name" syntax was used), overwrite
+/// name with ext_name and clear ext_name. When only creating an import
+/// library and not linking, the internal name is irrelevant.
+fn take_ext_names(def: &mut ModuleDef) {
+    for export in &mut def.exports {
+        if let Some(ext_name) = export.ext_name.take() {
+            export.name = ext_name;
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::def::{ModuleDef, ShortExport};
+use crate::Error;
+
+/// Bounds on the size of untrusted inputs and of the generated archive
+///
+/// `None` means unlimited, which is the default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum size of the .def text in bytes
+    pub max_def_size: Option<usize>,
+    /// Maximum number of exports, counting the native exports of ARM64X
+    pub max_exports: Option<usize>,
+    /// Maximum length in bytes of a symbol or DLL name
+    pub max_name_len: Option<usize>,
+    /// Maximum size of the written import library in bytes
+    ///
+    /// Libraries with too many exports to fit fail before any member is
+    /// built.
+    pub max_output_size: Option<usize>,
+}
+
+/// The limit an input or output exceeded, see [`Limits`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    DefSize,
+    Exports,
+    NameLength,
+    OutputSize,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DefSize => "def size",
+            Self::Exports => "number of exports",
+            Self::NameLength => "name length",
+            Self::OutputSize => "output size",
+        })
+    }
+}
+
+fn check(max: Option<usize>, limit: Limit, value: usize) -> Result<(), Error> {
+    match max {
+        Some(max) if value > max => Err(Error::LimitExceeded { limit, max }),
+        _ => Ok(()),
+    }
+}
+
+impl Limits {
+    pub(crate) fn check_def_size(&self, size: usize) -> Result<(), Error> {
+        check(self.max_def_size, Limit::DefSize, size)
+    }
+
+    pub(crate) fn check_exports(&self, count: usize) -> Result<(), Error> {
+        check(self.max_exports, Limit::Exports, count)
+    }
+
+    pub(crate) fn check_name(&self, name: &str) -> Result<(), Error> {
+        check(self.max_name_len, Limit::NameLength, name.len())
+    }
+
+    pub(crate) fn check_export(&self, export: &ShortExport) -> Result<(), Error> {
+        let names = [
+            Some(&export.name),
+            export.ext_name.as_ref(),
+            Some(&export.symbol_name),
+            Some(&export.alias_target),
+            export.export_as.as_ref(),
+        ];
+        for name in names.into_iter().flatten() {
+            self.check_name(name)?;
+        }
+        Ok(())
+    }
+
+    /// Check the exports and names of the defs written to one library,
+    /// and that it can fit in the output size before building any member
+    pub(crate) fn check_defs<'a>(
+        &self,
+        defs: impl IntoIterator<Item = &'a ModuleDef>,
+    ) -> Result<(), Error> {
+        let mut count = 0;
+        let mut min_size = ARCHIVE_MAGIC_SIZE;
+        for def in defs {
+            self.check_name(&def.import_name)?;
+            count += def.exports.len();
+            self.check_exports(count)?;
+            for export in &def.exports {
+                self.check_export(export)?;
+                min_size += min_member_size(export);
+            }
+        }
+        check(self.max_output_size, Limit::OutputSize, min_size)
+    }
+}
+
+const ARCHIVE_MAGIC_SIZE: usize = 8;
+const MEMBER_HEADER_SIZE: usize = 60;
+/// Size of both the short import header and the COFF file header
+const OBJECT_HEADER_SIZE: usize = 20;
+
+/// Lower bound of the size of the archive member importing `export`, with
+/// either flavor: a member header, an object header and one of its names
+fn min_member_size(export: &ShortExport) -> usize {
+    let names = [
+        Some(&export.name),
+        export.ext_name.as_ref(),
+        Some(&export.symbol_name).filter(|name| !name.is_empty()),
+    ];
+    let name_len = names.into_iter().flatten().map(String::len).min();
+    MEMBER_HEADER_SIZE + OBJECT_HEADER_SIZE + name_len.unwrap_or_default()
+}
+
+/// Writer failing once more than [`Limits::max_output_size`] bytes would be
+/// written past its start position, seeks are passed through unchanged
+#[derive(Debug)]
+pub(crate) struct LimitedWriter<'a, W> {
+    inner: &'a mut W,
+    max: Option<usize>,
+    start: u64,
+    /// Position relative to `start`
+    pos: u64,
+    exceeded: bool,
+}
+
+impl<'a, W: Write + Seek> LimitedWriter<'a, W> {
+    pub fn new(inner: &'a mut W, max: Option<usize>) -> Result<Self, Error> {
+        let start = match max {
+            Some(_) => inner.stream_position()?,
+            None => 0,
+        };
+        Ok(Self {
+            inner,
+            max,
+            start,
+            pos: 0,
+            exceeded: false,
+        })
+    }
+
+    /// Turn the I/O error caused by exceeding the limit into
+    /// [`Error::LimitExceeded`]
+    pub fn finish(&self, result: Result<(), Error>) -> Result<(), Error> {
+        match (result, self.max) {
+            (Err(_), Some(max)) if self.exceeded => Err(Error::LimitExceeded {
+                limit: Limit::OutputSize,
+                max,
+            }),
+            (result, _) => result,
+        }
+    }
+}
+
+impl<W: Write> Write for LimitedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(max) = self.max {
+            if self.pos + buf.len() as u64 > max as u64 {
+                self.exceeded = true;
+                return Err(io::Error::other("output size limit exceeded"));
+            }
+        }
+        let written = self.inner.write(buf)?;
+        self.pos += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for LimitedWriter<'_, W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let absolute = self.inner.seek(pos)?;
+        self.pos = absolute.saturating_sub(self.start);
+        Ok(absolute)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_limited_writer() {
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.write_all(b"head").unwrap();
+        let mut writer = LimitedWriter::new(&mut cursor, Some(4)).unwrap();
+        writer.write_all(b"abc").unwrap();
+        assert_eq!(writer.seek(SeekFrom::Start(4)).unwrap(), 4);
+        writer.write_all(b"wxyz").unwrap();
+        let result = writer.write_all(b"!").map_err(Error::from);
+        assert!(matches!(
+            writer.finish(result),
+            Err(Error::LimitExceeded {
+                limit: Limit::OutputSize,
+                max: 4
+            })
+        ));
+        assert_eq!(cursor.into_inner(), b"headwxyz");
+
+        // Unlimited writers don't query the position
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = LimitedWriter::new(&mut cursor, None).unwrap();
+        writer.write_all(&[0; 64]).unwrap();
+        assert!(writer.finish(Ok(())).is_ok());
+    }
+}
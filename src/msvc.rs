@@ -1,9 +1,15 @@
-use std::io::{Error, Seek, Write};
+use std::io::{Cursor, Seek, Write};
 
-use ar_archive_writer::{write_import_library, COFFShortExport, MachineTypes};
+use ar_archive_writer::{
+    write_archive_to_stream, write_import_library, ArchiveKind, COFFShortExport, MachineTypes,
+    NewArchiveMember,
+};
+use object::pe::{IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_ARM64EC};
+use object::read::archive::ArchiveFile;
+use object::{Object, ObjectSymbol};
 
 use crate::def::{ModuleDef, ShortExport};
-use crate::MachineType;
+use crate::{ArchiveMember, ArchiveWriter, Error, MachineType, WriteOptions};
 
 impl MachineType {
     fn to_ar_machine(self) -> MachineTypes {
@@ -32,7 +38,7 @@ fn convert_export(export: &ShortExport) -> COFFShortExport {
         } else {
             Some(export.alias_target.clone())
         },
-        export_as: None,
+        export_as: export.export_as.clone(),
         ordinal: export.ordinal,
         noname: export.no_name,
         data: export.data,
@@ -41,6 +47,15 @@ fn convert_export(export: &ShortExport) -> COFFShortExport {
     }
 }
 
+/// Reject names `ar_archive_writer` can't derive an import symbol from
+fn check_export(export: &ShortExport) -> Result<(), Error> {
+    let names = [Some(&export.name), export.ext_name.as_ref()];
+    if names.iter().flatten().any(|name| name.is_empty()) {
+        return Err(Error::validation("empty export name"));
+    }
+    Ok(())
+}
+
 /// MSVC flavored Windows import library generator
 #[derive(Debug, Clone)]
 pub struct MsvcImportLibrary {
@@ -59,7 +74,23 @@ impl MsvcImportLibrary {
     }
 
     /// Write out the import library
-    pub fn write_to<W: Write + Seek>(&self, writer: &mut W) -> Result<(), Error> {
+    pub fn write_to<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> Result<(), Error> {
+        if options.has_default_headers() {
+            self.write_import_library(writer)
+        } else {
+            write_members(writer, self.members()?, self.machine, options)
+        }
+    }
+
+    fn write_import_library<W: Write + Seek>(&self, writer: &mut W) -> Result<(), Error> {
+        let native_exports = self.native_def.iter().flat_map(|nd| &nd.exports);
+        for export in self.def.exports.iter().chain(native_exports) {
+            check_export(export)?;
+        }
         let exports: Vec<COFFShortExport> = self.def.exports.iter().map(convert_export).collect();
         let native_exports: Vec<COFFShortExport> = self
             .native_def
@@ -67,14 +98,217 @@ impl MsvcImportLibrary {
             .map(|nd| nd.exports.iter().map(convert_export).collect())
             .unwrap_or_default();
         let machine = self.machine.to_ar_machine();
-        write_import_library(
-            writer,
+        let mut writer = ArchiveWriter::new(writer);
+        let result = write_import_library(
+            &mut writer,
             &self.def.import_name,
             &exports,
             machine,
             false,
             false,
             &native_exports,
-        )
+        );
+        writer.finish(result)
     }
+
+    /// Generate the archive members of the import library
+    ///
+    /// `ar_archive_writer` only builds whole archives, the members are read
+    /// back from the one it writes.
+    fn members(&self) -> Result<Vec<ArchiveMember>, Error> {
+        let mut buf = Cursor::new(Vec::new());
+        self.write_import_library(&mut buf)?;
+        let data = buf.into_inner();
+        let archive = ArchiveFile::parse(&*data)?;
+        let mut members = Vec::new();
+        for member in archive.members() {
+            let member = member?;
+            // Skip the `/<ECSYMBOLS>/` symbol table, it is rebuilt on write.
+            if member.name().starts_with(b"/") {
+                continue;
+            }
+            members.push(ArchiveMember {
+                name: String::from_utf8_lossy(member.name()).into_owned(),
+                data: member.data(&*data)?.to_vec(),
+            });
+        }
+        Ok(members)
+    }
+}
+
+/// Write out an archive of import members, like `write_import_library`
+/// but with the member headers of `options`
+fn write_members<W: Write + Seek>(
+    writer: &mut W,
+    members: Vec<ArchiveMember>,
+    machine: MachineType,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    let archive_members: Vec<NewArchiveMember> = members
+        .into_iter()
+        .map(|m| options.archive_member(m.data, m.name))
+        .collect();
+    let is_ec = matches!(machine, MachineType::ARM64EC | MachineType::ARM64X);
+    let mut writer = ArchiveWriter::new(writer);
+    let result = write_archive_to_stream(
+        &mut writer,
+        &archive_members,
+        ArchiveKind::Coff,
+        false,
+        Some(is_ec),
+    );
+    writer.finish(result)
+}
+
+/// Write out an import library bundling the imports of several DLLs
+pub fn write_umbrella_to<W: Write + Seek>(
+    defs: Vec<(ModuleDef, Option<ModuleDef>)>,
+    machine: MachineType,
+    writer: &mut W,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    let mut members = Vec::new();
+    let mut has_null_import_descriptor = false;
+    for (def, native_def) in defs {
+        for member in MsvcImportLibrary::new(def, native_def, machine).members()? {
+            // Every DLL comes with a `__NULL_IMPORT_DESCRIPTOR` member, the
+            // linker only needs one of them.
+            if is_null_import_descriptor(&member.data) {
+                if has_null_import_descriptor {
+                    continue;
+                }
+                has_null_import_descriptor = true;
+            }
+            members.push(member);
+        }
+    }
+    write_members(writer, members, machine, options)
+}
+
+/// Rewrite an MSVC import library to import from `dll_name`
+///
+/// The DLL name in the short import members is patched in place, keeping
+/// their symbols, ordinals and name types. The import descriptor and null
+/// thunk members are regenerated, other members are kept as is.
+pub fn retarget_to<W: Write + Seek>(
+    data: &[u8],
+    dll_name: &str,
+    writer: &mut W,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    let archive = ArchiveFile::parse(data)?;
+    let mut members = Vec::new();
+    let mut old_dll_name: Option<Vec<u8>> = None;
+    let mut descriptor_machine = None;
+    let mut import_machines = Vec::new();
+    // Where the regenerated descriptor members go
+    let mut descriptor_index = None;
+    for member in archive.members() {
+        let member = member?;
+        if member.name().starts_with(b"/") {
+            continue;
+        }
+        let name = String::from_utf8_lossy(member.name()).into_owned();
+        let member_data = member.data(data)?;
+        if member_data.starts_with(&[0, 0, 0xff, 0xff]) {
+            let (patched, old) = retarget_short_import(member_data, dll_name)?;
+            match &old_dll_name {
+                Some(existing) if !existing.eq_ignore_ascii_case(old) => {
+                    return Err(Error::validation("import library references several DLLs"))
+                }
+                Some(_) => {}
+                None => old_dll_name = Some(old.to_vec()),
+            }
+            import_machines.push(u16::from_le_bytes([member_data[6], member_data[7]]));
+            // Members are named after the DLL.
+            let name = if name.as_bytes().eq_ignore_ascii_case(old) {
+                dll_name.to_string()
+            } else {
+                name
+            };
+            members.push(ArchiveMember {
+                name,
+                data: patched,
+            });
+            continue;
+        }
+        if let Ok(file) = object::File::parse(member_data) {
+            let is_descriptor = |name: &[u8]| {
+                name.starts_with(b"__IMPORT_DESCRIPTOR_")
+                    || name == b"__NULL_IMPORT_DESCRIPTOR"
+                    || name.starts_with(b"\x7f") && name.ends_with(b"_NULL_THUNK_DATA")
+            };
+            let defined: Vec<_> = file
+                .symbols()
+                .filter(|sym| sym.is_global() && !sym.is_undefined())
+                .filter_map(|sym| sym.name_bytes().ok())
+                .collect();
+            if defined.iter().any(|name| is_descriptor(name)) {
+                if defined
+                    .iter()
+                    .any(|name| name.starts_with(b"__IMPORT_DESCRIPTOR_"))
+                {
+                    descriptor_machine = Some(u16::from_le_bytes([member_data[0], member_data[1]]));
+                }
+                descriptor_index.get_or_insert(members.len());
+                continue;
+            }
+        }
+        members.push(ArchiveMember {
+            name,
+            data: member_data.to_vec(),
+        });
+    }
+
+    let machine = descriptor_machine
+        .or(import_machines.first().copied())
+        .and_then(MachineType::from_u16)
+        .ok_or_else(|| Error::validation("no imports found"))?;
+    // ARM64EC imports come with ARM64 descriptors.
+    let has_ec = import_machines.contains(&IMAGE_FILE_MACHINE_ARM64EC);
+    let machine = match machine {
+        MachineType::ARM64 if has_ec && import_machines.contains(&IMAGE_FILE_MACHINE_ARM64) => {
+            MachineType::ARM64X
+        }
+        MachineType::ARM64 if has_ec => MachineType::ARM64EC,
+        machine => machine,
+    };
+    let def = ModuleDef {
+        import_name: dll_name.to_string(),
+        ..Default::default()
+    };
+    let descriptors = MsvcImportLibrary::new(def, None, machine).members()?;
+    let index = descriptor_index.unwrap_or(0);
+    members.splice(index..index, descriptors);
+
+    write_members(writer, members, machine, options)
+}
+
+/// Replace the DLL name of a short import, returning it with the old name
+fn retarget_short_import<'a>(data: &'a [u8], dll_name: &str) -> Result<(Vec<u8>, &'a [u8]), Error> {
+    let invalid = || Error::encoding("invalid short import member");
+    let header = data.get(..20).ok_or_else(invalid)?;
+    let size = u32::from_le_bytes([header[12], header[13], header[14], header[15]]) as usize;
+    let strings = data.get(20..20 + size).ok_or_else(invalid)?;
+    let symbol_end = memchr::memchr(0, strings).ok_or_else(invalid)? + 1;
+    let dll_end = memchr::memchr(0, &strings[symbol_end..]).ok_or_else(invalid)? + symbol_end;
+    let old = &strings[symbol_end..dll_end];
+
+    let mut patched = Vec::with_capacity(data.len() + dll_name.len());
+    patched.extend_from_slice(&header[..12]);
+    let size = size - old.len() + dll_name.len();
+    patched.extend_from_slice(&(size as u32).to_le_bytes());
+    patched.extend_from_slice(&header[16..]);
+    patched.extend_from_slice(&strings[..symbol_end]);
+    patched.extend_from_slice(dll_name.as_bytes());
+    // The NUL and, for EXPORTAS imports, the export name
+    patched.extend_from_slice(&strings[dll_end..]);
+    Ok((patched, old))
+}
+
+fn is_null_import_descriptor(data: &[u8]) -> bool {
+    object::File::parse(data).is_ok_and(|file| {
+        file.symbols()
+            .any(|sym| !sym.is_undefined() && sym.name_bytes() == Ok(b"__NULL_IMPORT_DESCRIPTOR"))
+    })
 }
@@ -0,0 +1,109 @@
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+use ar_archive_writer::{NewArchiveMember, DEFAULT_OBJECT_READER};
+
+use crate::def::ModuleDef;
+
+/// Archive layout options of [`ImportLibrary`](crate::ImportLibrary)
+///
+/// The output only depends on the module definitions, the machine type, the
+/// flavor and these options. The defaults match the archives written by
+/// previous releases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Modification time of every member, in seconds since the Unix epoch
+    pub mtime: u64,
+    /// Owner user id of every member
+    pub uid: u32,
+    /// Owner group id of every member
+    pub gid: u32,
+    /// File mode of every member
+    pub mode: u32,
+    /// Order of the exports, and so of their symbols, in the archive
+    pub symbol_order: SymbolOrder,
+    /// Names of the per-export members of GNU import libraries
+    pub gnu_member_names: GnuMemberNames,
+    /// File name of GNU import libraries, like dlltool's `--output-lib`,
+    /// defaults to `<dll name>.a`
+    ///
+    /// Only used to derive the member prefix when
+    /// [`gnu_member_prefix`](Self::gnu_member_prefix) is unset: every
+    /// character but ASCII letters and digits is replaced by `_` and a `_`
+    /// is appended, as dlltool derives its temp prefix.
+    pub gnu_archive_name: Option<String>,
+    /// Prefix of the member names of GNU import libraries, like dlltool's
+    /// `--temp-prefix`, as in `<prefix>h.o` and `<prefix>s00000.o`
+    pub gnu_member_prefix: Option<String>,
+    /// Stem of the `_head_<stem>` and `<stem>_iname` symbols of GNU import
+    /// libraries, defaults to the DLL name with `.` replaced by `_`
+    ///
+    /// Libraries importing from DLLs with the same name need different
+    /// stems to be linked together.
+    pub gnu_symbol_stem: Option<String>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            mtime: 0,
+            uid: 0,
+            gid: 0,
+            mode: 0o644,
+            symbol_order: SymbolOrder::Definition,
+            gnu_member_names: GnuMemberNames::Sequential,
+            gnu_archive_name: None,
+            gnu_member_prefix: None,
+            gnu_symbol_stem: None,
+        }
+    }
+}
+
+/// Order of the exports in the archive, see [`WriteOptions::symbol_order`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymbolOrder {
+    /// As listed in the module definition
+    #[default]
+    Definition,
+    /// Sorted by name, so that reordering the module definition doesn't
+    /// change the archive
+    Name,
+}
+
+/// Naming of the per-export members of GNU import libraries, see
+/// [`WriteOptions::gnu_member_names`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GnuMemberNames {
+    /// Numbered in export order like dlltool, as in `foo_dll_a_s00000.o`
+    #[default]
+    Sequential,
+    /// Named after the export, as in `foo_dll_a_e_bar.o`, so that adding or
+    /// removing exports doesn't rename the members of the others
+    ExportName,
+}
+
+impl WriteOptions {
+    /// Whether the member headers are the ones `ar_archive_writer` writes
+    /// by default
+    #[cfg(feature = "msvc")]
+    pub(crate) fn has_default_headers(&self) -> bool {
+        let default = Self::default();
+        (self.mtime, self.uid, self.gid, self.mode)
+            == (default.mtime, default.uid, default.gid, default.mode)
+    }
+
+    #[cfg(any(feature = "msvc", feature = "gnu"))]
+    pub(crate) fn archive_member(&self, data: Vec<u8>, name: String) -> NewArchiveMember<'static> {
+        let mut member = NewArchiveMember::new(data, &DEFAULT_OBJECT_READER, name);
+        member.mtime = self.mtime;
+        member.uid = self.uid;
+        member.gid = self.gid;
+        member.perms = self.mode;
+        member
+    }
+
+    pub(crate) fn order_exports(&self, def: &mut ModuleDef) {
+        match self.symbol_order {
+            SymbolOrder::Definition => {}
+            SymbolOrder::Name => def.exports.sort_by(|a, b| a.name.cmp(&b.name)),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::io::Write;
+
+use object::pe::*;
+use object::write::pe::{NtHeaders, Writer};
+
+use crate::def::{ModuleDef, ShortExport};
+use crate::edata;
+use crate::{Error, MachineType};
+
+const SECTION_ALIGNMENT: u32 = 0x1000;
+const FILE_ALIGNMENT: u32 = 0x200;
+/// Size of the code stub and the data slot backing each export
+const SLOT_SIZE: u32 = 8;
+
+/// Stub DLL generator
+///
+/// Produces a minimal DLL whose export table matches a module definition.
+/// Code exports point to functions returning zero and `DATA`/`CONSTANT`
+/// exports to zero-initialized slots, so the DLL can be loaded and its
+/// exports resolved but not meaningfully used.
+///
+/// ARM64EC produces an x64 image and ARM64X a native ARM64 image, no hybrid
+/// metadata is generated.
+#[derive(Debug, Clone)]
+pub struct StubDll {
+    def: ModuleDef,
+    machine: MachineType,
+}
+
+impl StubDll {
+    /// Create new stub DLL generator from module definition text content
+    pub fn new(def: &str, machine: MachineType) -> Result<Self, Error> {
+        let def = ModuleDef::parse(def, machine)?;
+        Ok(Self::from_def(def, machine))
+    }
+
+    /// Create new stub DLL generator from `ModuleDef`
+    pub fn from_def(def: ModuleDef, machine: MachineType) -> Self {
+        StubDll { def, machine }
+    }
+
+    /// Write out the DLL
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let exports = edata::exports(&self.def, self.machine)?;
+        let mut directory = edata::build(&self.def.import_name, &exports);
+        let machine = match self.machine {
+            MachineType::ARM64EC => MachineType::AMD64,
+            MachineType::ARM64X => MachineType::ARM64,
+            machine => machine,
+        };
+        let is_64 = !matches!(machine, MachineType::I386 | MachineType::ARMNT);
+
+        let is_data = |export: &ShortExport| export.data || export.constant;
+        let data_count = directory
+            .symbols
+            .iter()
+            .filter(|(_, export)| is_data(export))
+            .count() as u32;
+        let code_count = directory.symbols.len() as u32 - data_count;
+
+        let mut buffer = Vec::new();
+        let mut pe = Writer::new(is_64, SECTION_ALIGNMENT, FILE_ALIGNMENT, &mut buffer);
+        pe.reserve_dos_header_and_stub();
+        pe.reserve_nt_headers(IMAGE_NUMBEROF_DIRECTORY_ENTRIES);
+        pe.reserve_section_headers(1 + u16::from(code_count > 0) + u16::from(data_count > 0));
+
+        let text = (code_count > 0).then(|| pe.reserve_text_section(code_count * SLOT_SIZE));
+        // Data slots are zero-initialized, so they take no space in the file.
+        let data = (data_count > 0).then(|| pe.reserve_data_section(data_count * SLOT_SIZE, 0));
+        let edata = pe.reserve_edata_section(directory.data.len() as u32);
+
+        let mut code = Vec::new();
+        let mut data_slots = 0;
+        for &(offset, export) in &directory.symbols {
+            let rva = if is_data(export) {
+                let rva = data.as_ref().unwrap().virtual_address + data_slots * SLOT_SIZE;
+                data_slots += 1;
+                rva
+            } else {
+                let rva = text.as_ref().unwrap().virtual_address + code.len() as u32;
+                code.extend_from_slice(&return_zero(machine, self.stack_bytes(export)));
+                code.resize(code.len().next_multiple_of(SLOT_SIZE as usize), 0);
+                if machine == MachineType::ARMNT {
+                    // Thumb entry point
+                    rva | 1
+                } else {
+                    rva
+                }
+            };
+            write_u32(&mut directory.data, offset, rva);
+        }
+        for &offset in &directory.internal {
+            let value = read_u32(&directory.data, offset) + edata.virtual_address;
+            write_u32(&mut directory.data, offset, value);
+        }
+
+        let to_u16 = |value: u32, what: &str| {
+            u16::try_from(value)
+                .map_err(|_| Error::validation(format!("{} out of range: {}", what, value)))
+        };
+        let (major_subsystem_version, minor_subsystem_version) = match machine {
+            MachineType::ARMNT | MachineType::ARM64 => (6, 2),
+            _ => (6, 0),
+        };
+        let mut dll_characteristics =
+            IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE | IMAGE_DLLCHARACTERISTICS_NX_COMPAT;
+        let mut characteristics = IMAGE_FILE_EXECUTABLE_IMAGE | IMAGE_FILE_DLL;
+        if is_64 {
+            dll_characteristics |= IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA;
+            characteristics |= IMAGE_FILE_LARGE_ADDRESS_AWARE;
+        } else {
+            characteristics |= IMAGE_FILE_32BIT_MACHINE;
+        }
+        let or_default = |value: u64, default: u64| if value == 0 { default } else { value };
+        let def = &self.def;
+        let (major_os_version, minor_os_version) =
+            match (def.major_os_version, def.minor_os_version) {
+                (0, 0) => (6, 0),
+                version => version,
+            };
+        let nt_headers = NtHeaders {
+            machine: machine as u16,
+            time_date_stamp: 0,
+            characteristics,
+            major_linker_version: 14,
+            minor_linker_version: 0,
+            // No DllMain
+            address_of_entry_point: 0,
+            image_base: or_default(
+                def.image_base,
+                if is_64 { 0x1_8000_0000 } else { 0x1000_0000 },
+            ),
+            major_operating_system_version: to_u16(major_os_version, "OS version")?,
+            minor_operating_system_version: to_u16(minor_os_version, "OS version")?,
+            major_image_version: to_u16(def.major_image_version, "image version")?,
+            minor_image_version: to_u16(def.minor_image_version, "image version")?,
+            major_subsystem_version,
+            minor_subsystem_version,
+            subsystem: IMAGE_SUBSYSTEM_WINDOWS_GUI,
+            dll_characteristics,
+            size_of_stack_reserve: or_default(def.stack_reserve, 0x10_0000),
+            size_of_stack_commit: or_default(def.stack_commit, 0x1000),
+            size_of_heap_reserve: or_default(def.heap_reserve, 0x10_0000),
+            size_of_heap_commit: or_default(def.heap_commit, 0x1000),
+        };
+        pe.write_dos_header_and_stub()?;
+        pe.write_nt_headers(nt_headers);
+        pe.write_section_headers();
+        if let Some(text) = &text {
+            pe.write_section(text.file_offset, &code);
+        }
+        pe.write_section(edata.file_offset, &directory.data);
+        writer.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Bytes of arguments an i386 function pops off the stack
+    fn stack_bytes(&self, export: &ShortExport) -> u16 {
+        // stdcall functions pop their arguments, "foo@8" pops 8 bytes.
+        // fastcall ("@foo@8") passes the first 8 bytes in registers.
+        match self.machine {
+            MachineType::I386 if !export.name.starts_with('?') => {
+                let bytes = export
+                    .name
+                    .rsplit_once('@')
+                    .and_then(|(_, bytes)| bytes.parse::<u16>().ok())
+                    .unwrap_or(0);
+                if export.name.starts_with('@') {
+                    bytes.saturating_sub(8)
+                } else {
+                    bytes
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Machine code of a function returning zero
+fn return_zero(machine: MachineType, stack_bytes: u16) -> Vec<u8> {
+    match machine {
+        MachineType::I386 if stack_bytes != 0 => {
+            // xor eax, eax; ret imm16
+            let [lo, hi] = stack_bytes.to_le_bytes();
+            vec![0x31, 0xc0, 0xc2, lo, hi]
+        }
+        // xor eax, eax; ret
+        MachineType::I386 | MachineType::AMD64 | MachineType::ARM64EC => vec![0x31, 0xc0, 0xc3],
+        // movs r0, #0; bx lr
+        MachineType::ARMNT => vec![0x00, 0x20, 0x70, 0x47],
+        // mov w0, #0; ret
+        MachineType::ARM64 | MachineType::ARM64X => {
+            vec![0x00, 0x00, 0x80, 0x52, 0xc0, 0x03, 0x5f, 0xd6]
+        }
+    }
+}
+
+fn read_u32(data: &[u8], offset: u32) -> u32 {
+    let offset = offset as usize;
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u32(data: &mut [u8], offset: u32, value: u32) {
+    let offset = offset as usize;
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
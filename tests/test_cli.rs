@@ -0,0 +1,264 @@
+#![cfg(feature = "cli")]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use implib::inspect::ImportLibraryInfo;
+use implib::{Flavor, ImportLibrary, MachineType};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("implib-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn implib(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_implib"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn expected(def: &str, machine: MachineType) -> Vec<u8> {
+    let mut lib = std::io::Cursor::new(Vec::new());
+    ImportLibrary::new(def, machine, Flavor::Msvc)
+        .unwrap()
+        .write_to(&mut lib)
+        .unwrap();
+    lib.into_inner()
+}
+
+#[test]
+fn test_cli_dlltool() {
+    let dir = temp_dir("dlltool");
+    let lib = dir.join("python39.lib");
+    let output = implib(&[
+        "-m",
+        "i386:x86-64",
+        "-d",
+        "tests/python39.def",
+        "-l",
+        lib.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read(&lib).unwrap(),
+        expected(include_str!("python39.def"), MachineType::AMD64)
+    );
+
+    // -D overrides the LIBRARY statement
+    let def = dir.join("foo.def");
+    std::fs::write(&def, "LIBRARY foo.dll\nEXPORTS\nbar\n").unwrap();
+    let output = implib(&[
+        "--machine=arm64",
+        &format!("--input-def={}", def.display()),
+        &format!("-l{}", lib.display()),
+        "-D",
+        "baz.dll",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read(&lib).unwrap(),
+        expected("LIBRARY baz.dll\nEXPORTS\nbar\n", MachineType::ARM64)
+    );
+
+    // -N builds an ARM64X library
+    let native_def = dir.join("native.def");
+    std::fs::write(&native_def, "LIBRARY foo.dll\nEXPORTS\nnative\n").unwrap();
+    let output = implib(&[
+        "-m",
+        "arm64ec",
+        "-d",
+        def.to_str().unwrap(),
+        "-N",
+        native_def.to_str().unwrap(),
+        "-l",
+        lib.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let mut arm64x = std::io::Cursor::new(Vec::new());
+    ImportLibrary::new_arm64x(
+        "LIBRARY foo.dll\nEXPORTS\nbar\n",
+        "LIBRARY foo.dll\nEXPORTS\nnative\n",
+        Flavor::Msvc,
+    )
+    .unwrap()
+    .write_to(&mut arm64x)
+    .unwrap();
+    assert_eq!(std::fs::read(&lib).unwrap(), arm64x.into_inner());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cli_dlltool_mingw_def() {
+    // Libraries written by llvm-dlltool 14 from the same .def file
+    let dir = temp_dir("dlltool-mingw");
+    for (args, reference) in [
+        (&[][..], &include_bytes!("llvm-dlltool/stdcall.lib")[..]),
+        (
+            &["-k"][..],
+            &include_bytes!("llvm-dlltool/stdcall-k.lib")[..],
+        ),
+    ] {
+        let lib = dir.join("stdcall.lib");
+        let mut cmd = vec!["-m", "i386", "-d", "tests/llvm-dlltool/stdcall.def"];
+        cmd.extend(args);
+        cmd.extend(["-l", lib.to_str().unwrap()]);
+        let output = implib(&cmd);
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(
+            ImportLibraryInfo::parse(&std::fs::read(&lib).unwrap()).unwrap(),
+            ImportLibraryInfo::parse(reference).unwrap(),
+            "{:?}",
+            args
+        );
+    }
+
+    // -k doesn't trip over names starting with a multibyte character
+    let def = dir.join("utf8.def");
+    std::fs::write(&def, "LIBRARY foo.dll\nEXPORTS\n\u{e9}@@foo@4\n").unwrap();
+    let lib = dir.join("utf8.lib");
+    let output = implib(&[
+        "-m",
+        "i386",
+        "-k",
+        "-d",
+        def.to_str().unwrap(),
+        "-l",
+        lib.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cli_dlltool_errors() {
+    let output = implib(&["-m", "mips", "-d", "tests/python39.def", "-l", "x.lib"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "implib: error: unknown machine: mips\n"
+    );
+
+    let output = implib(&["-d", "tests/python39.def"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "implib: error: no output file specified\n"
+    );
+}
+
+#[test]
+fn test_cli_lib() {
+    let dir = temp_dir("lib");
+    let lib = dir.join("python39.lib");
+    let output = implib(&[
+        "lib",
+        "/NOLOGO",
+        "/DEF:tests/python39.def",
+        &format!("/OUT:{}", lib.display()),
+        "/machine:x64",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read(&lib).unwrap(),
+        expected(include_str!("python39.def"), MachineType::AMD64)
+    );
+
+    // Arguments from a response file, exports from the command line
+    let rsp = dir.join("args.rsp");
+    std::fs::write(
+        &rsp,
+        format!(
+            "-def -name:foo.dll\n\"-out:{}\" /EXPORT:bar /EXPORT:baz,@3,NONAME",
+            lib.display()
+        ),
+    )
+    .unwrap();
+    let output = implib(&["lib", &format!("@{}", rsp.display()), "/MACHINE:ARM64"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read(&lib).unwrap(),
+        expected(
+            "LIBRARY foo.dll\nEXPORTS\nbar\nbaz @3 NONAME\n",
+            MachineType::ARM64
+        )
+    );
+
+    // Exports from .drectve sections of objects
+    let mut obj = object::write::Object::new(
+        object::BinaryFormat::Coff,
+        object::Architecture::X86_64,
+        object::Endianness::Little,
+    );
+    let section = obj.add_section(
+        Vec::new(),
+        b".drectve".to_vec(),
+        object::SectionKind::Linker,
+    );
+    obj.append_section_data(section, b"/EXPORT:foo /EXPORT:bar,DATA", 1);
+    let obj_path = dir.join("foo.obj");
+    std::fs::write(&obj_path, obj.write().unwrap()).unwrap();
+    let output = implib(&[
+        "lib",
+        "/DEF",
+        "/NAME:foo.dll",
+        "/MACHINE:X64",
+        &format!("/OUT:{}", lib.display()),
+        obj_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read(&lib).unwrap(),
+        expected(
+            "LIBRARY foo.dll\nEXPORTS\nfoo\nbar DATA\n",
+            MachineType::AMD64
+        )
+    );
+
+    // i386 names from .drectve sections are mangled like /EXPORT ones
+    let mut obj = object::write::Object::new(
+        object::BinaryFormat::Coff,
+        object::Architecture::I386,
+        object::Endianness::Little,
+    );
+    let section = obj.add_section(
+        Vec::new(),
+        b".drectve".to_vec(),
+        object::SectionKind::Linker,
+    );
+    obj.append_section_data(section, b"/EXPORT:foo /EXPORT:bar@4", 1);
+    std::fs::write(&obj_path, obj.write().unwrap()).unwrap();
+    let output = implib(&[
+        "lib",
+        "/DEF",
+        "/NAME:foo.dll",
+        "/MACHINE:X86",
+        "/EXPORT:foo",
+        &format!("/OUT:{}", lib.display()),
+        obj_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read(&lib).unwrap(),
+        expected("LIBRARY foo.dll\nEXPORTS\nfoo\nbar@4\n", MachineType::I386)
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cli_lib_errors() {
+    let output = implib(&["lib", "/OUT:foo.lib", "foo.obj"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "implib: error: only /DEF mode is supported\n"
+    );
+
+    let output = implib(&["lib", "/DEF", "/NAME:foo.dll", "/LTCG"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "implib: error: unknown argument: /LTCG\n"
+    );
+}
@@ -0,0 +1,224 @@
+#![cfg(all(feature = "msvc", feature = "gnu"))]
+
+use object::read::archive::ArchiveFile;
+
+use implib::def::ModuleDef;
+use implib::{Flavor, GnuMemberNames, ImportLibrary, MachineType, SymbolOrder, WriteOptions};
+
+const DEF: &str = "LIBRARY python39.dll
+EXPORTS
+Py_Initialize @1
+Py_Finalize @2
+PyObject_Free @7 NONAME
+Py_Version DATA
+PyWin_Callback@8
+Py_Alias == Py_Initialize
+";
+
+const MACHINES: [MachineType; 6] = [
+    MachineType::I386,
+    MachineType::AMD64,
+    MachineType::ARMNT,
+    MachineType::ARM64,
+    MachineType::ARM64EC,
+    MachineType::ARM64X,
+];
+
+fn import_lib(def: &str, machine: MachineType, flavor: Flavor) -> ImportLibrary {
+    if machine == MachineType::ARM64X {
+        ImportLibrary::new_arm64x(def, def, flavor).unwrap()
+    } else {
+        ImportLibrary::new(def, machine, flavor).unwrap()
+    }
+}
+
+/// FNV-1a, stable across platforms and Rust releases
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+fn member_names(data: &[u8]) -> Vec<String> {
+    ArchiveFile::parse(data)
+        .unwrap()
+        .members()
+        .map(|member| String::from_utf8_lossy(member.unwrap().name()).into_owned())
+        .filter(|name| !name.starts_with('/'))
+        .collect()
+}
+
+/// Identical inputs produce identical bytes, on every host and in every
+/// process
+#[test]
+fn test_reproducible_output() {
+    let mut hashes = Vec::new();
+    for flavor in [Flavor::Msvc, Flavor::Gnu] {
+        for machine in MACHINES {
+            let lib = import_lib(DEF, machine, flavor);
+            let data = lib.write_to_vec().unwrap();
+            assert_eq!(lib.write_to_vec().unwrap(), data);
+            // Hash maps are seeded differently in every instance and thread.
+            let other = std::thread::spawn(move || {
+                import_lib(DEF, machine, flavor).write_to_vec().unwrap()
+            })
+            .join()
+            .unwrap();
+            assert_eq!(other, data, "{:?} {:?}", flavor, machine);
+
+            let archive = ArchiveFile::parse(&*data).unwrap();
+            for member in archive.members() {
+                let member = member.unwrap();
+                let name = String::from_utf8_lossy(member.name());
+                assert_eq!(member.date(), Some(0), "{}", name);
+                assert_eq!(member.uid(), Some(0), "{}", name);
+                assert_eq!(member.gid(), Some(0), "{}", name);
+                if !name.starts_with('/') {
+                    assert_eq!(member.mode(), Some(0o644), "{}", name);
+                    assert!(!name.contains(['/', '\\']), "{}", name);
+                }
+            }
+            hashes.push((flavor, machine, fnv1a(&data)));
+        }
+    }
+    // Golden digests of the output. Changing the format breaks reproducible
+    // builds against older releases, so these are only updated deliberately,
+    // together with the change to the format.
+    let expected = [
+        (Flavor::Msvc, MachineType::I386, 0x1746d338b5688b4d),
+        (Flavor::Msvc, MachineType::AMD64, 0x5381a84971e2495b),
+        (Flavor::Msvc, MachineType::ARMNT, 0x4c9e5a7d08752bd6),
+        (Flavor::Msvc, MachineType::ARM64, 0x29709d6c3ab5bdfa),
+        (Flavor::Msvc, MachineType::ARM64EC, 0xe68a3d0b81de8cfa),
+        (Flavor::Msvc, MachineType::ARM64X, 0x1f2307b82c64f80),
+        (Flavor::Gnu, MachineType::I386, 0xbee9b8f37872eb5c),
+        (Flavor::Gnu, MachineType::AMD64, 0x16d2338d0593493e),
+        (Flavor::Gnu, MachineType::ARMNT, 0x21c017435c6f9288),
+        (Flavor::Gnu, MachineType::ARM64, 0xcdd5de7393e35398),
+        (Flavor::Gnu, MachineType::ARM64EC, 0xcdd5de7393e35398),
+        (Flavor::Gnu, MachineType::ARM64X, 0xcdd5de7393e35398),
+    ];
+    assert_eq!(hashes.len(), expected.len());
+    for ((flavor, machine, hash), (_, _, expected)) in hashes.iter().zip(expected) {
+        assert_eq!(*hash, expected, "{:?} {:?} {:#x}", flavor, machine, hash);
+    }
+}
+
+#[test]
+fn test_gnu_member_names() {
+    let lib = ImportLibrary::new(DEF, MachineType::AMD64, Flavor::Gnu).unwrap();
+    assert_eq!(
+        member_names(&lib.write_to_vec().unwrap()),
+        [
+            "python39_dll_a_t.o",
+            "python39_dll_a_h.o",
+            "python39_dll_a_s00005.o",
+            "python39_dll_a_s00004.o",
+            "python39_dll_a_s00003.o",
+            "python39_dll_a_s00002.o",
+            "python39_dll_a_s00001.o",
+            "python39_dll_a_s00000.o",
+        ]
+    );
+
+    let options = WriteOptions {
+        gnu_member_names: GnuMemberNames::ExportName,
+        ..Default::default()
+    };
+    let write = |def: &str| {
+        let lib = ImportLibrary::new(def, MachineType::AMD64, Flavor::Gnu).unwrap();
+        member_names(&lib.with_options(options.clone()).write_to_vec().unwrap())
+    };
+    let names = write(DEF);
+    assert_eq!(
+        names[2..],
+        [
+            "python39_dll_a_e_Py_Alias.o",
+            "python39_dll_a_e_PyWin_Callback_8.o",
+            "python39_dll_a_e_Py_Version.o",
+            "python39_dll_a_e_PyObject_Free.o",
+            "python39_dll_a_e_Py_Finalize.o",
+            "python39_dll_a_e_Py_Initialize.o",
+        ]
+    );
+    // Adding an export doesn't rename the others
+    let added = write(&DEF.replace("Py_Finalize @2\n", "Py_Finalize @2\nPy_Main\n"));
+    assert!(names.iter().all(|name| added.contains(name)));
+    // Names stay unique
+    let names = write("LIBRARY a.dll\nEXPORTS\nfoo@4\nfoo_4\n");
+    assert_eq!(names[2..], ["a_dll_a_e_foo_4_1.o", "a_dll_a_e_foo_4.o"]);
+}
+
+#[test]
+fn test_symbol_order() {
+    let reordered = "LIBRARY python39.dll
+EXPORTS
+PyWin_Callback@8
+Py_Version DATA
+Py_Alias == Py_Initialize
+Py_Finalize @2
+PyObject_Free @7 NONAME
+Py_Initialize @1
+";
+    let options = WriteOptions {
+        symbol_order: SymbolOrder::Name,
+        ..Default::default()
+    };
+    for flavor in [Flavor::Msvc, Flavor::Gnu] {
+        for machine in MACHINES {
+            let write = |def: &str, options: &WriteOptions| {
+                import_lib(def, machine, flavor)
+                    .with_options(options.clone())
+                    .write_to_vec()
+                    .unwrap()
+            };
+            assert_eq!(write(DEF, &options), write(reordered, &options));
+            assert_ne!(
+                write(DEF, &WriteOptions::default()),
+                write(reordered, &WriteOptions::default())
+            );
+        }
+    }
+
+    // Sorted like a module definition listing the exports by name
+    let mut def = ModuleDef::parse(DEF, MachineType::AMD64).unwrap();
+    def.exports.sort_by(|a, b| a.name.cmp(&b.name));
+    let sorted = ImportLibrary::from_def(def, MachineType::AMD64, Flavor::Gnu)
+        .write_to_vec()
+        .unwrap();
+    let lib = ImportLibrary::new(DEF, MachineType::AMD64, Flavor::Gnu).unwrap();
+    assert_eq!(lib.with_options(options).write_to_vec().unwrap(), sorted);
+}
+
+#[test]
+fn test_member_headers() {
+    let options = WriteOptions {
+        mtime: 1_700_000_000,
+        uid: 1000,
+        gid: 100,
+        mode: 0o600,
+        ..Default::default()
+    };
+    for flavor in [Flavor::Msvc, Flavor::Gnu] {
+        for machine in MACHINES {
+            let lib = import_lib(DEF, machine, flavor);
+            let default = lib.write_to_vec().unwrap();
+            let data = lib.with_options(options.clone()).write_to_vec().unwrap();
+            // Only the headers change
+            assert_eq!(member_names(&data), member_names(&default));
+            let archive = ArchiveFile::parse(&*data).unwrap();
+            let default_archive = ArchiveFile::parse(&*default).unwrap();
+            for (member, default_member) in archive.members().zip(default_archive.members()) {
+                let (member, default_member) = (member.unwrap(), default_member.unwrap());
+                assert_eq!(member.data(&*data), default_member.data(&*default));
+                if member.name().starts_with(b"/") {
+                    continue;
+                }
+                assert_eq!(member.date(), Some(options.mtime));
+                assert_eq!(member.uid(), Some(1000));
+                assert_eq!(member.gid(), Some(100));
+                assert_eq!(member.mode(), Some(0o600));
+            }
+        }
+    }
+}
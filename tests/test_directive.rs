@@ -0,0 +1,50 @@
+use implib::def::ModuleDef;
+use object::write::Object;
+use object::{Architecture, BinaryFormat, Endianness, SectionKind};
+
+fn coff_object(directives: &[u8]) -> Vec<u8> {
+    let mut obj = Object::new(BinaryFormat::Coff, Architecture::X86_64, Endianness::Little);
+    let section = obj.add_section(Vec::new(), b".drectve".to_vec(), SectionKind::Linker);
+    obj.append_section_data(section, directives, 1);
+    obj.write().unwrap()
+}
+
+#[test]
+fn test_from_drectve() {
+    let a = coff_object(b" /DEFAULTLIB:\"LIBCMT\" /EXPORT:foo /EXPORT:bar,DATA");
+    let b = coff_object(b"\xef\xbb\xbf-export:foo -export:baz=qux,@4,NONAME /merge:.x=.y");
+    let def = ModuleDef::from_drectve(&[&a, &b]).unwrap();
+    assert_eq!(def.import_name, "");
+    let exports: Vec<_> = def
+        .exports
+        .iter()
+        .map(|e| (e.ext_name.as_deref(), e.name.as_str(), e.ordinal, e.data))
+        .collect();
+    assert_eq!(
+        exports,
+        [
+            (None, "foo", 0, false),
+            (None, "bar", 0, true),
+            (Some("baz"), "qux", 4, false),
+        ]
+    );
+    assert!(def.exports[2].no_name);
+}
+
+#[test]
+fn test_from_drectve_errors() {
+    let a = coff_object(b"/EXPORT:foo");
+    let b = coff_object(b"/EXPORT:foo,DATA");
+    let err = ModuleDef::from_drectve(&[&a, &b]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "conflicting export foo in fragment 0 and fragment 1"
+    );
+
+    let bad = coff_object(b"/EXPORT:foo,@x");
+    let err = ModuleDef::from_drectve(&[&bad]).unwrap_err();
+    assert_eq!(err.to_string(), "invalid /EXPORT: foo,@x");
+
+    let err = ModuleDef::from_drectve(&[b"garbage"]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,158 @@
+use std::error::Error as _;
+use std::io::{self, Cursor};
+
+use implib::def::ModuleDef;
+use implib::inspect::ImportLibraryInfo;
+use implib::{Error, Flavor, ImportLibrary, MachineType};
+
+#[test]
+fn test_parse_error() {
+    let err =
+        ImportLibrary::new("EXPORTS\nfoo @ bar\n", MachineType::AMD64, Flavor::Msvc).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Parse {
+            line: 2,
+            column: 7,
+            ..
+        }
+    ));
+    assert_eq!(err.to_string(), "line 2, column 7: invalid ordinal: bar");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_validation_error() {
+    let import_lib = ImportLibrary::new(
+        "LIBRARY \"foo\0.dll\"\nEXPORTS\nbar\n",
+        MachineType::AMD64,
+        Flavor::Gnu,
+    )
+    .unwrap();
+    let err = import_lib
+        .write_to(&mut Cursor::new(Vec::new()))
+        .unwrap_err();
+    assert!(matches!(err, Error::Validation(_)), "{:?}", err);
+}
+
+#[test]
+fn test_encoding_error() {
+    let err = ImportLibraryInfo::parse(b"not an archive").unwrap_err();
+    assert!(matches!(err, Error::Encoding(_)), "{:?}", err);
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    // The object error is kept as the source
+    let source = err.source().unwrap();
+    assert!(source.downcast_ref::<object::read::Error>().is_some());
+}
+
+#[test]
+fn test_io_error_conversion() {
+    let err =
+        ImportLibrary::new("EXPORTS\nfoo @ bar\n", MachineType::AMD64, Flavor::Msvc).unwrap_err();
+    let message = err.to_string();
+    let io_err = io::Error::from(err);
+    assert_eq!(io_err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(io_err.to_string(), message);
+    let inner = io_err.into_inner().unwrap();
+    assert!(matches!(
+        inner.downcast_ref::<Error>(),
+        Some(Error::Parse { .. })
+    ));
+
+    // I/O errors are passed through unwrapped
+    let err = Error::from(io::Error::new(io::ErrorKind::WriteZero, "disk full"));
+    let io_err = io::Error::from(err);
+    assert_eq!(io_err.kind(), io::ErrorKind::WriteZero);
+    assert_eq!(io_err.to_string(), "disk full");
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_empty_export_name(flavor: Flavor) {
+    for machine in [MachineType::AMD64, MachineType::ARM64EC] {
+        let mut def = ModuleDef::parse("LIBRARY foo.dll\nEXPORTS\nbar\n", machine).unwrap();
+        def.exports[0].name.clear();
+        let result =
+            ImportLibrary::from_def(def, machine, flavor).write_to(&mut Cursor::new(Vec::new()));
+        assert!(matches!(result, Err(Error::Validation(_))), "{:?}", result);
+    }
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_empty_export_name_msvc() {
+    check_empty_export_name(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_empty_export_name_gnu() {
+    check_empty_export_name(Flavor::Gnu);
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_arm64ec_mangling_error() {
+    for (name, message) in [
+        (
+            "?foo",
+            "Functions on Arm64EC must use the Arm64EC mangling scheme",
+        ),
+        ("?foo@@$$h", "Invalid ARM64EC function name"),
+    ] {
+        let def = format!("LIBRARY foo.dll\nEXPORTS\n{}\n", name);
+        let import_lib = ImportLibrary::new(&def, MachineType::ARM64EC, Flavor::Msvc).unwrap();
+        let err = import_lib
+            .write_to(&mut Cursor::new(Vec::new()))
+            .unwrap_err();
+        assert!(matches!(err, Error::Validation(_)), "{:?}", err);
+        assert!(err.to_string().starts_with(message), "{}", err);
+    }
+}
+
+/// Writer failing every write like a full disk
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+struct FullDisk;
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+impl io::Write for FullDisk {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+impl io::Seek for FullDisk {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_writer_io_error(flavor: Flavor) {
+    let import_lib = ImportLibrary::new(
+        "LIBRARY foo.dll\nEXPORTS\nbar\n",
+        MachineType::AMD64,
+        flavor,
+    )
+    .unwrap();
+    let err = import_lib.write_to(&mut FullDisk).unwrap_err();
+    assert!(matches!(err, Error::Io(_)), "{:?}", err);
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_writer_io_error_msvc() {
+    check_writer_io_error(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_writer_io_error_gnu() {
+    check_writer_io_error(Flavor::Gnu);
+}
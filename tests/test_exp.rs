@@ -0,0 +1,124 @@
+#![cfg(feature = "exp")]
+
+use std::collections::HashMap;
+
+use implib::{ExportObject, MachineType};
+use object::read::pe::{ExportTable, ExportTarget};
+use object::{Object, ObjectSection, ObjectSymbol, RelocationTarget};
+
+const DEF: &str = "LIBRARY foo.dll
+EXPORTS
+bar @3
+baz DATA
+qux @5 NONAME
+alias = bar
+fwd = other.target
+";
+
+/// Link the `.edata` section of an export object at `SECTION_RVA`, placing
+/// each undefined symbol at its address in `symbols`, and return the
+/// exports as (ordinal, name, target)
+fn link(exp: &[u8], symbols: &HashMap<&str, u32>) -> Vec<(u32, Option<String>, String)> {
+    const SECTION_RVA: u32 = 0x1000;
+    let file = object::File::parse(exp).unwrap();
+    let section = file.section_by_name(".edata").unwrap();
+    let mut data = section.data().unwrap().to_vec();
+    for (offset, relocation) in section.relocations() {
+        let RelocationTarget::Symbol(index) = relocation.target() else {
+            panic!("unexpected relocation target");
+        };
+        let symbol = file.symbol_by_index(index).unwrap();
+        let address = if symbol.is_undefined() {
+            symbols[symbol.name().unwrap()]
+        } else {
+            SECTION_RVA
+        };
+        let offset = offset as usize;
+        let addend = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        data[offset..offset + 4].copy_from_slice(&(address + addend).to_le_bytes());
+    }
+
+    let table = ExportTable::parse(&data, SECTION_RVA).unwrap();
+    let mut exports: Vec<_> = table
+        .exports()
+        .unwrap()
+        .into_iter()
+        .filter(|export| !matches!(export.target, ExportTarget::Address(0)))
+        .map(|export| {
+            let name = export
+                .name
+                .map(|name| String::from_utf8(name.to_vec()).unwrap());
+            let target = match export.target {
+                ExportTarget::Address(address) => format!("{:#x}", address),
+                ExportTarget::ForwardByName(lib, name) => format!(
+                    "{}.{}",
+                    String::from_utf8_lossy(lib),
+                    String::from_utf8_lossy(name)
+                ),
+                ExportTarget::ForwardByOrdinal(lib, ordinal) => {
+                    format!("{}.#{}", String::from_utf8_lossy(lib), ordinal)
+                }
+            };
+            (export.ordinal, name, target)
+        })
+        .collect();
+    exports.sort();
+    let name = table.directory().name.get(object::LittleEndian);
+    assert_eq!(table.name_from_pointer(name).unwrap(), b"foo.dll");
+    exports
+}
+
+#[test]
+fn test_exp() {
+    for (machine, prefix) in [
+        (MachineType::AMD64, ""),
+        (MachineType::I386, "_"),
+        (MachineType::ARMNT, ""),
+        (MachineType::ARM64, ""),
+        (MachineType::ARM64EC, ""),
+        (MachineType::ARM64X, ""),
+    ] {
+        let mut exp = Vec::new();
+        ExportObject::new(DEF, machine)
+            .unwrap()
+            .write_to(&mut exp)
+            .unwrap();
+        let file = object::File::parse(&*exp).unwrap();
+        assert_eq!(file.format(), object::BinaryFormat::Coff);
+
+        let mut undefined: Vec<String> = file
+            .symbols()
+            .filter(|symbol| symbol.is_undefined())
+            .map(|symbol| symbol.name().unwrap().to_string())
+            .collect();
+        undefined.sort();
+        let names = |names: &[&str]| -> Vec<String> {
+            names.iter().map(|n| format!("{}{}", prefix, n)).collect()
+        };
+        assert_eq!(undefined, names(&["bar", "baz", "qux"]), "{:?}", machine);
+
+        let bar = format!("{}bar", prefix);
+        let baz = format!("{}baz", prefix);
+        let qux = format!("{}qux", prefix);
+        let symbols = HashMap::from([
+            (bar.as_str(), 0x2000),
+            (baz.as_str(), 0x3000),
+            (qux.as_str(), 0x4000),
+        ]);
+        let named = |ordinal, name: &str, target: &str| {
+            (ordinal, Some(name.to_string()), target.to_string())
+        };
+        assert_eq!(
+            link(&exp, &symbols),
+            [
+                named(3, "bar", "0x2000"),
+                (5, None, "0x4000".to_string()),
+                named(6, "baz", "0x3000"),
+                named(7, "alias", "0x2000"),
+                named(8, "fwd", "other.target"),
+            ],
+            "{:?}",
+            machine
+        );
+    }
+}
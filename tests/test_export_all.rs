@@ -0,0 +1,210 @@
+use implib::def::{ExportAllOptions, ModuleDef};
+use implib::inspect::ImportLibraryInfo;
+use implib::{Flavor, ImportLibrary, MachineType};
+use object::write::{Mangling, Object, Symbol, SymbolSection};
+use object::{
+    Architecture, BinaryFormat, Endianness, SectionKind, SymbolFlags, SymbolKind, SymbolScope,
+};
+
+fn coff_object(arch: Architecture, text: &[&str], data: &[&str], locals: &[&str]) -> Vec<u8> {
+    let mut obj = Object::new(BinaryFormat::Coff, arch, Endianness::Little);
+    obj.mangling = Mangling::None;
+    let text_sec = obj.section_id(object::write::StandardSection::Text);
+    obj.append_section_data(text_sec, &[0xc3; 16], 16);
+    let data_sec = obj.add_section(Vec::new(), b".data".to_vec(), SectionKind::Data);
+    obj.append_section_data(data_sec, &[0; 16], 16);
+    let symbols = text
+        .iter()
+        .map(|name| (name, text_sec, SymbolScope::Dynamic))
+        .chain(
+            data.iter()
+                .map(|name| (name, data_sec, SymbolScope::Dynamic)),
+        )
+        .chain(
+            locals
+                .iter()
+                .map(|name| (name, text_sec, SymbolScope::Compilation)),
+        );
+    for (name, section, scope) in symbols {
+        obj.add_symbol(Symbol {
+            name: name.as_bytes().to_vec(),
+            value: 0,
+            size: 0,
+            kind: if section == text_sec {
+                SymbolKind::Text
+            } else {
+                SymbolKind::Data
+            },
+            scope,
+            weak: false,
+            section: SymbolSection::Section(section),
+            flags: SymbolFlags::None,
+        });
+    }
+    obj.write().unwrap()
+}
+
+/// GNU archive without a symbol table, member names must be short
+fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut ar = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            format!("{}/", name),
+            0,
+            0,
+            0,
+            644,
+            data.len()
+        );
+        ar.extend_from_slice(header.as_bytes());
+        ar.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            ar.push(b'\n');
+        }
+    }
+    ar
+}
+
+fn exports(def: &ModuleDef) -> Vec<(&str, bool)> {
+    def.exports
+        .iter()
+        .map(|e| (e.name.as_str(), e.data))
+        .collect()
+}
+
+#[test]
+fn test_export_all() {
+    let a = coff_object(
+        Architecture::X86_64,
+        &["foo", "DllMain", "__imp_bar", "bar", "_head_libfoo"],
+        &["counter", ".refptr.counter", "libfoo_NULL_THUNK_DATA"],
+        &["helper"],
+    );
+    let b = coff_object(Architecture::X86_64, &["foo", "baz"], &[], &[]);
+    let def = ModuleDef::export_all(
+        &[("a.o", &a), ("b.obj", &b)],
+        MachineType::AMD64,
+        &ExportAllOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(def.import_name, "");
+    assert_eq!(
+        exports(&def),
+        [("foo", false), ("counter", true), ("baz", false)]
+    );
+
+    let def = ModuleDef::export_all(
+        &[("a.o", &a)],
+        MachineType::AMD64,
+        &ExportAllOptions {
+            exclude_symbols: vec!["counter".to_string()],
+            no_default_excludes: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        exports(&def),
+        [
+            ("foo", false),
+            ("DllMain", false),
+            ("__imp_bar", false),
+            ("_head_libfoo", false),
+            (".refptr.counter", true),
+            ("libfoo_NULL_THUNK_DATA", true),
+        ]
+    );
+}
+
+#[test]
+fn test_export_all_i386() {
+    let obj = coff_object(
+        Architecture::I386,
+        &["_foo", "_bar@8", "@fast@4", "_DllMain@12", "__head_libfoo"],
+        &["_counter"],
+        &[],
+    );
+    let def = ModuleDef::export_all(
+        &[("foo.o", &obj)],
+        MachineType::I386,
+        &ExportAllOptions {
+            exclude_symbols: vec!["counter".to_string()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        exports(&def),
+        [("_foo", false), ("_bar@8", false), ("@fast@4", false)]
+    );
+
+    // The library defines the object symbols and their `__imp_` pointers
+    let def = ModuleDef {
+        import_name: "foo.dll".to_string(),
+        ..def
+    };
+    for flavor in [
+        #[cfg(feature = "msvc")]
+        Flavor::Msvc,
+        #[cfg(feature = "gnu")]
+        Flavor::Gnu,
+    ] {
+        let mut lib = std::io::Cursor::new(Vec::new());
+        ImportLibrary::from_def(def.clone(), MachineType::I386, flavor)
+            .write_to(&mut lib)
+            .unwrap();
+        let info = ImportLibraryInfo::parse(lib.get_ref()).unwrap();
+        let symbols: Vec<&str> = info
+            .members
+            .iter()
+            .flat_map(|member| member.symbols.iter().map(String::as_str))
+            .collect();
+        for symbol in ["_foo", "_bar@8", "@fast@4"] {
+            assert!(symbols.contains(&symbol), "{:?}: {}", flavor, symbol);
+            let imp = format!("__imp_{}", symbol);
+            assert!(symbols.contains(&imp.as_str()), "{:?}: {}", flavor, imp);
+        }
+    }
+}
+
+#[test]
+fn test_export_all_archives() {
+    let foo = coff_object(Architecture::X86_64, &["foo"], &[], &[]);
+    let bar = coff_object(Architecture::X86_64, &["bar"], &[], &[]);
+    let crt = coff_object(Architecture::X86_64, &["mainCRTStartup"], &[], &[]);
+    let lib = archive(&[("foo.o", &foo), ("bar.o", &bar)]);
+    let gcc = archive(&[("crt.o", &crt)]);
+    let inputs: [(&str, &[u8]); 4] = [
+        ("lib/libfoo.a", &lib),
+        ("lib/libgcc.a", &gcc),
+        ("crt2.o", &crt),
+        ("obj/crt.o", &crt),
+    ];
+
+    let def =
+        ModuleDef::export_all(&inputs, MachineType::AMD64, &ExportAllOptions::default()).unwrap();
+    assert_eq!(
+        exports(&def),
+        [("foo", false), ("bar", false), ("mainCRTStartup", false)]
+    );
+
+    let options = ExportAllOptions {
+        exclude_libs: vec!["libfoo".to_string()],
+        ..Default::default()
+    };
+    let def = ModuleDef::export_all(&inputs[..2], MachineType::AMD64, &options).unwrap();
+    assert!(def.exports.is_empty());
+}
+
+#[test]
+fn test_export_all_errors() {
+    let err = ModuleDef::export_all(
+        &[("bogus.o", b"garbage")],
+        MachineType::AMD64,
+        &ExportAllOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().starts_with("bogus.o: "));
+}
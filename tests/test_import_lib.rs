@@ -1,4 +1,6 @@
-use implib::{Flavor, ImportLibrary, MachineType};
+use implib::def::ModuleDef;
+use implib::inspect::ImportLibraryInfo;
+use implib::{Flavor, ImportLibrary, MachineType, UmbrellaImportLibrary};
 
 #[cfg(feature = "msvc")]
 #[test]
@@ -162,3 +164,374 @@ fn test_import_library_msvc_arm64x() {
         "expected many native ARM64 symbols in the regular symbol table, got {regular_num_symbols}"
     );
 }
+
+/// Forwarders only matter to the DLL, importers see a plain export.
+#[cfg(all(feature = "msvc", feature = "gnu"))]
+#[test]
+fn test_import_library_forwarder() {
+    for flavor in [Flavor::Msvc, Flavor::Gnu] {
+        for machine in [MachineType::AMD64, MachineType::I386] {
+            let write = |def: &str| {
+                let mut lib = std::io::Cursor::new(Vec::new());
+                ImportLibrary::new(def, machine, flavor)
+                    .unwrap()
+                    .write_to(&mut lib)
+                    .unwrap();
+                lib.into_inner()
+            };
+            assert_eq!(
+                write("LIBRARY k32.dll\nEXPORTS\nHeapAlloc = NTDLL.RtlAllocateHeap\n"),
+                write("LIBRARY k32.dll\nEXPORTS\nHeapAlloc = RtlAllocateHeap\n"),
+            );
+        }
+    }
+}
+
+/// Stream that can't seek, like stdout or a pipe
+#[cfg(all(feature = "msvc", feature = "gnu"))]
+struct Unseekable(Vec<u8>);
+
+#[cfg(all(feature = "msvc", feature = "gnu"))]
+impl std::io::Write for Unseekable {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "msvc", feature = "gnu"))]
+#[test]
+fn test_import_library_write_unseekable() {
+    for flavor in [Flavor::Msvc, Flavor::Gnu] {
+        let import_lib =
+            ImportLibrary::new(include_str!("python39.def"), MachineType::AMD64, flavor).unwrap();
+        let mut lib = std::io::Cursor::new(Vec::new());
+        import_lib.write_to(&mut lib).unwrap();
+        let lib = lib.into_inner();
+
+        // The same library can be written several times
+        assert_eq!(import_lib.write_to_vec().unwrap(), lib);
+        let mut stream = Unseekable(Vec::new());
+        import_lib.write_unseekable(&mut stream).unwrap();
+        assert_eq!(stream.0, lib);
+        assert!(ImportLibraryInfo::parse(&stream.0).is_ok());
+    }
+}
+
+fn umbrella_defs(machine: MachineType) -> Vec<ModuleDef> {
+    vec![
+        ModuleDef::parse("LIBRARY foo.dll\nEXPORTS\nfoo_a\nfoo_b DATA\n", machine).unwrap(),
+        ModuleDef::parse("LIBRARY bar.dll\nEXPORTS\nbar_a\n", machine).unwrap(),
+        ModuleDef::parse("LIBRARY baz.dll\nEXPORTS\nbaz_a @1 NONAME\n", machine).unwrap(),
+    ]
+}
+
+fn check_umbrella(data: &[u8]) {
+    let info = ImportLibraryInfo::parse(data).unwrap();
+    for (dll, symbol) in [
+        ("foo.dll", "__imp_foo_a"),
+        ("foo.dll", "__imp_foo_b"),
+        ("bar.dll", "__imp_bar_a"),
+        ("baz.dll", "__imp_baz_a"),
+    ] {
+        let member = info
+            .members
+            .iter()
+            .find(|m| m.symbols.iter().any(|s| s == symbol))
+            .unwrap_or_else(|| panic!("missing {}", symbol));
+        assert_eq!(member.dll_name.as_deref(), Some(dll));
+    }
+
+    // The archive symbol table must cover the imports of every DLL.
+    let archive = object::read::archive::ArchiveFile::parse(data).unwrap();
+    let symbols: Vec<&[u8]> = archive
+        .symbols()
+        .unwrap()
+        .unwrap()
+        .map(|s| s.unwrap().name())
+        .collect();
+    for symbol in [&b"__imp_foo_a"[..], b"foo_a", b"__imp_bar_a", b"baz_a"] {
+        assert!(symbols.contains(&symbol));
+    }
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_umbrella_import_library_msvc() {
+    let mut lib = std::io::Cursor::new(Vec::new());
+    UmbrellaImportLibrary::new(
+        umbrella_defs(MachineType::AMD64),
+        MachineType::AMD64,
+        Flavor::Msvc,
+    )
+    .write_to(&mut lib)
+    .unwrap();
+    let data = lib.into_inner();
+    check_umbrella(&data);
+
+    let info = ImportLibraryInfo::parse(&data).unwrap();
+    let null_import_descriptors = info
+        .members
+        .iter()
+        .filter(|m| m.symbols == ["__NULL_IMPORT_DESCRIPTOR"])
+        .count();
+    assert_eq!(null_import_descriptors, 1);
+    for dll in ["foo", "bar", "baz"] {
+        let descriptor = format!("__IMPORT_DESCRIPTOR_{}", dll);
+        assert!(info
+            .members
+            .iter()
+            .any(|m| m.symbols == [descriptor.clone()]));
+    }
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_umbrella_import_library_gnu() {
+    let mut lib = std::io::Cursor::new(Vec::new());
+    UmbrellaImportLibrary::new(
+        umbrella_defs(MachineType::AMD64),
+        MachineType::AMD64,
+        Flavor::Gnu,
+    )
+    .write_to(&mut lib)
+    .unwrap();
+    let data = lib.into_inner();
+    check_umbrella(&data);
+
+    let info = ImportLibraryInfo::parse(&data).unwrap();
+    let mut names: Vec<&str> = info.members.iter().map(|m| m.name.as_str()).collect();
+    let count = names.len();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), count, "member names must be unique");
+}
+
+#[test]
+fn test_umbrella_import_library_duplicate_dll() {
+    let defs = vec![
+        ModuleDef::parse("LIBRARY foo.dll\nEXPORTS\na\n", MachineType::AMD64).unwrap(),
+        ModuleDef::parse("LIBRARY FOO.dll\nEXPORTS\nb\n", MachineType::AMD64).unwrap(),
+    ];
+    let mut lib = std::io::Cursor::new(Vec::new());
+    let err = UmbrellaImportLibrary::new(defs, MachineType::AMD64, Flavor::Msvc)
+        .write_to(&mut lib)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "duplicate import name: FOO.dll");
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_umbrella_import_library_gnu_member_clash() {
+    let defs = vec![
+        ModuleDef::parse("LIBRARY foo.dll\nEXPORTS\na\n", MachineType::AMD64).unwrap(),
+        ModuleDef::parse("LIBRARY foo_dll\nEXPORTS\nb\n", MachineType::AMD64).unwrap(),
+    ];
+    let mut lib = std::io::Cursor::new(Vec::new());
+    UmbrellaImportLibrary::new(defs, MachineType::AMD64, Flavor::Gnu)
+        .write_to(&mut lib)
+        .unwrap_err();
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_import_library_gnu_naming() {
+    use implib::WriteOptions;
+
+    let write = |options: WriteOptions| {
+        let lib = ImportLibrary::new(
+            "LIBRARY python39.dll\nEXPORTS\nPy_Initialize\n",
+            MachineType::AMD64,
+            Flavor::Gnu,
+        )
+        .unwrap()
+        .with_options(options);
+        ImportLibraryInfo::parse(&lib.write_to_vec()?)
+    };
+    let names = |info: &ImportLibraryInfo| {
+        info.members
+            .iter()
+            .map(|m| m.name.clone())
+            .filter(|name| !name.starts_with('/'))
+            .collect::<Vec<_>>()
+    };
+
+    // Member names derived from the archive name like dlltool's temp prefix
+    let info = write(WriteOptions {
+        gnu_archive_name: Some("libpython3.9-x.dll.a".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+        names(&info),
+        [
+            "libpython3_9_x_dll_a_t.o",
+            "libpython3_9_x_dll_a_h.o",
+            "libpython3_9_x_dll_a_s00000.o",
+        ]
+    );
+
+    // An explicit prefix wins over the archive name
+    let info = write(WriteOptions {
+        gnu_archive_name: Some("libpython39.a".to_string()),
+        gnu_member_prefix: Some("py".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(names(&info), ["pyt.o", "pyh.o", "pys00000.o"]);
+
+    // The symbol stem doesn't change the imported DLL
+    let info = write(WriteOptions {
+        gnu_symbol_stem: Some("python39_x64".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+        names(&info),
+        [
+            "python39_dll_a_t.o",
+            "python39_dll_a_h.o",
+            "python39_dll_a_s00000.o",
+        ]
+    );
+    assert!(info.members[0]
+        .symbols
+        .contains(&"python39_x64_iname".to_string()));
+    assert!(info.members[1]
+        .symbols
+        .contains(&"_head_python39_x64".to_string()));
+    let export = &info.members[2];
+    assert_eq!(export.dll_name.as_deref(), Some("python39.dll"));
+    assert!(export.symbols.contains(&"Py_Initialize".to_string()));
+
+    for options in [
+        WriteOptions {
+            gnu_member_prefix: Some("lib/py".to_string()),
+            ..Default::default()
+        },
+        WriteOptions {
+            gnu_symbol_stem: Some(String::new()),
+            ..Default::default()
+        },
+    ] {
+        assert!(write(options).is_err());
+    }
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_umbrella_import_library_arm64x() {
+    let parse = |def: &str, machine| ModuleDef::parse(def, machine).unwrap();
+    let defs = ["foo", "bar"]
+        .into_iter()
+        .map(|dll| {
+            let def = format!("LIBRARY {dll}.dll\nEXPORTS\n{dll}_ec\n");
+            let native_def = format!("LIBRARY {dll}.dll\nEXPORTS\n{dll}_native\n");
+            (
+                parse(&def, MachineType::ARM64EC),
+                parse(&native_def, MachineType::ARM64),
+            )
+        })
+        .collect();
+    let data = UmbrellaImportLibrary::new_arm64x(defs, Flavor::Msvc)
+        .write_to_vec()
+        .unwrap();
+    let info = ImportLibraryInfo::parse(&data).unwrap();
+    for dll in ["foo", "bar"] {
+        let symbol = format!("__imp_{}_native", dll);
+        let member = info
+            .members
+            .iter()
+            .find(|m| m.symbols.contains(&symbol))
+            .unwrap_or_else(|| panic!("missing {}", symbol));
+        assert_eq!(member.machine, Some(MachineType::ARM64));
+        assert_eq!(member.dll_name, Some(format!("{}.dll", dll)));
+        let symbol = format!("__imp_{}_ec", dll);
+        assert!(info.members.iter().any(|m| m.symbols.contains(&symbol)));
+    }
+}
+
+#[test]
+fn test_umbrella_import_library_options() {
+    use implib::{Error, Limit, Limits, SymbolOrder, WriteOptions};
+
+    let options = WriteOptions {
+        mtime: 1_700_000_000,
+        symbol_order: SymbolOrder::Name,
+        ..Default::default()
+    };
+    let mut flavors = Vec::new();
+    if cfg!(feature = "msvc") {
+        flavors.push(Flavor::Msvc);
+    }
+    if cfg!(feature = "gnu") {
+        flavors.push(Flavor::Gnu);
+    }
+    for flavor in flavors {
+        let umbrella = |defs| {
+            UmbrellaImportLibrary::new(defs, MachineType::AMD64, flavor)
+                .with_options(options.clone())
+        };
+        let lib = umbrella(umbrella_defs(MachineType::AMD64));
+        let data = lib.write_to_vec().unwrap();
+        assert_eq!(lib.write_to_vec().unwrap(), data);
+        check_umbrella(&data);
+        let archive = object::read::archive::ArchiveFile::parse(&*data).unwrap();
+        for member in archive.members() {
+            let member = member.unwrap();
+            if !member.name().starts_with(b"/") {
+                assert_eq!(member.date(), Some(options.mtime));
+            }
+        }
+
+        // Independent of the export order
+        let mut defs = umbrella_defs(MachineType::AMD64);
+        defs[0].exports.reverse();
+        assert_eq!(umbrella(defs).write_to_vec().unwrap(), data);
+
+        let limits = Limits {
+            max_exports: Some(3),
+            ..Default::default()
+        };
+        let result = lib.clone().with_limits(limits).write_to_vec();
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded {
+                limit: Limit::Exports,
+                ..
+            })
+        ));
+        let limits = Limits {
+            max_output_size: Some(data.len() - 1),
+            ..Default::default()
+        };
+        let result = lib.clone().with_limits(limits).write_to_vec();
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded {
+                limit: Limit::OutputSize,
+                ..
+            })
+        ));
+    }
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_umbrella_import_library_gnu_naming() {
+    let options = implib::WriteOptions {
+        gnu_symbol_stem: Some("sdk".to_string()),
+        ..Default::default()
+    };
+    UmbrellaImportLibrary::new(
+        umbrella_defs(MachineType::AMD64),
+        MachineType::AMD64,
+        Flavor::Gnu,
+    )
+    .with_options(options)
+    .write_to_vec()
+    .unwrap_err();
+}
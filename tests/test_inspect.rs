@@ -0,0 +1,245 @@
+use implib::inspect::{ImportLibraryInfo, ImportNameType, ImportType, MemberInfo};
+use implib::{Flavor, ImportLibrary, MachineType};
+
+const DEF: &str = "LIBRARY foo.dll
+EXPORTS
+bar @3
+baz DATA
+qux @7 NONAME
+";
+
+fn build(def: &str, machine: MachineType, flavor: Flavor) -> ImportLibraryInfo {
+    let import_lib = ImportLibrary::new(def, machine, flavor).unwrap();
+    let mut lib = std::io::Cursor::new(Vec::new());
+    import_lib.write_to(&mut lib).unwrap();
+    ImportLibraryInfo::parse(&lib.into_inner()).unwrap()
+}
+
+fn imports(info: &ImportLibraryInfo) -> Vec<&MemberInfo> {
+    info.members
+        .iter()
+        .filter(|m| m.import_type.is_some())
+        .collect()
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_inspect_msvc_amd64() {
+    let info = build(DEF, MachineType::AMD64, Flavor::Msvc);
+    assert!(info
+        .members
+        .iter()
+        .all(|m| m.machine == Some(MachineType::AMD64)));
+    assert!(info
+        .members
+        .iter()
+        .any(|m| m.symbols == ["__IMPORT_DESCRIPTOR_foo"]
+            && m.dll_name.as_deref() == Some("foo.dll")));
+
+    let imports = imports(&info);
+    assert_eq!(imports.len(), 3);
+    for member in &imports {
+        assert_eq!(member.name, "foo.dll");
+        assert_eq!(member.dll_name.as_deref(), Some("foo.dll"));
+    }
+
+    assert_eq!(imports[0].import_type, Some(ImportType::Code));
+    assert_eq!(imports[0].name_type, Some(ImportNameType::Name));
+    assert_eq!(imports[0].hint, 3);
+    assert_eq!(imports[0].import_name.as_deref(), Some("bar"));
+    assert_eq!(imports[0].symbols, ["__imp_bar", "bar"]);
+
+    assert_eq!(imports[1].import_type, Some(ImportType::Data));
+    assert_eq!(imports[1].name_type, Some(ImportNameType::Name));
+    assert_eq!(imports[1].symbols, ["__imp_baz"]);
+
+    assert_eq!(imports[2].name_type, Some(ImportNameType::Ordinal));
+    assert_eq!(imports[2].hint, 7);
+    assert_eq!(imports[2].import_name, None);
+    assert_eq!(imports[2].symbols, ["__imp_qux", "qux"]);
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_inspect_msvc_i386() {
+    let info = build(
+        "LIBRARY foo.dll\nEXPORTS\nbar\n_baz@4\n",
+        MachineType::I386,
+        Flavor::Msvc,
+    );
+    let imports = imports(&info);
+    assert_eq!(imports.len(), 2);
+    assert_eq!(imports[0].machine, Some(MachineType::I386));
+    assert_eq!(imports[0].name_type, Some(ImportNameType::NoPrefix));
+    assert_eq!(imports[0].import_name.as_deref(), Some("bar"));
+    assert_eq!(imports[0].symbols, ["__imp__bar", "_bar"]);
+    assert_eq!(imports[1].name_type, Some(ImportNameType::Name));
+    assert_eq!(imports[1].import_name.as_deref(), Some("_baz@4"));
+    assert_eq!(imports[1].symbols, ["__imp__baz@4", "_baz@4"]);
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_inspect_msvc_arm64ec() {
+    let info = build(
+        "LIBRARY foo.dll\nEXPORTS\nbar\n",
+        MachineType::ARM64EC,
+        Flavor::Msvc,
+    );
+    let imports = imports(&info);
+    assert_eq!(imports.len(), 1);
+    assert_eq!(imports[0].machine, Some(MachineType::ARM64EC));
+    assert_eq!(imports[0].name_type, Some(ImportNameType::ExportAs));
+    assert_eq!(imports[0].import_name.as_deref(), Some("bar"));
+    assert_eq!(
+        imports[0].symbols,
+        ["__imp_bar", "bar", "__imp_aux_bar", "#bar"]
+    );
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_inspect_gnu_amd64() {
+    let info = build(DEF, MachineType::AMD64, Flavor::Gnu);
+    assert!(info
+        .members
+        .iter()
+        .all(|m| m.machine == Some(MachineType::AMD64)));
+    assert!(info
+        .members
+        .iter()
+        .all(|m| m.dll_name.as_deref() == Some("foo.dll")));
+
+    let imports = imports(&info);
+    assert_eq!(imports.len(), 3);
+    let find = |symbol: &str| {
+        *imports
+            .iter()
+            .find(|m| m.symbols.iter().any(|s| s == symbol))
+            .unwrap()
+    };
+
+    let bar = find("bar");
+    assert_eq!(bar.import_type, Some(ImportType::Code));
+    assert_eq!(bar.name_type, Some(ImportNameType::Name));
+    assert_eq!(bar.hint, 3);
+    assert_eq!(bar.import_name.as_deref(), Some("bar"));
+    assert_eq!(bar.symbols, ["bar", "__imp_bar"]);
+
+    let baz = find("__imp_baz");
+    assert_eq!(baz.import_type, Some(ImportType::Data));
+    assert_eq!(baz.symbols, ["__imp_baz"]);
+
+    let qux = find("qux");
+    assert_eq!(qux.name_type, Some(ImportNameType::Ordinal));
+    assert_eq!(qux.hint, 7);
+    assert_eq!(qux.import_name, None);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_inspect_gnu_i386() {
+    let info = build(
+        "LIBRARY foo.dll\nEXPORTS\nbar\n",
+        MachineType::I386,
+        Flavor::Gnu,
+    );
+    let imports = imports(&info);
+    assert_eq!(imports.len(), 1);
+    assert_eq!(imports[0].machine, Some(MachineType::I386));
+    assert_eq!(imports[0].dll_name.as_deref(), Some("foo.dll"));
+    assert_eq!(imports[0].import_name.as_deref(), Some("bar"));
+    assert_eq!(imports[0].symbols, ["_bar", "__imp__bar"]);
+}
+
+#[test]
+fn test_inspect_bad_input() {
+    ImportLibraryInfo::parse(b"not an archive").unwrap_err();
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_inspect_to_module_def() {
+    let info = build(DEF, MachineType::AMD64, Flavor::Msvc);
+    let def = info.to_module_def();
+    assert_eq!(def.import_name, "foo.dll");
+    let names: Vec<&str> = def.exports.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["bar", "baz", "qux"]);
+    assert!(def.exports[1].data);
+    assert!(def.exports[2].no_name);
+    assert_eq!(def.exports[2].ordinal, 7);
+
+    let parsed = implib::def::ModuleDef::parse(DEF, MachineType::AMD64).unwrap();
+    assert!(parsed.diff(&def).changes.is_empty());
+}
+
+#[cfg(all(feature = "msvc", feature = "gnu"))]
+#[test]
+fn test_inspect_diff_libraries() {
+    let old = build(DEF, MachineType::AMD64, Flavor::Msvc).to_module_def();
+    let new = build(
+        "LIBRARY foo.dll\nEXPORTS\nbar @3\nbaz\nqux @8 NONAME\nquux\n",
+        MachineType::AMD64,
+        Flavor::Gnu,
+    )
+    .to_module_def();
+    assert_eq!(
+        old.diff(&new).to_string(),
+        "breaking\tkind\tbaz\tdata\tcode\n\
+         breaking\tordinal\tqux\t7\t8\n\
+         compatible\tadded\tquux\n"
+    );
+}
+
+#[cfg(feature = "stub")]
+#[test]
+fn test_inspect_dll() {
+    use implib::def::{ForwardTarget, ModuleDef};
+    use implib::StubDll;
+
+    for machine in [MachineType::AMD64, MachineType::I386] {
+        let def = ModuleDef::parse(
+            "LIBRARY foo.dll
+VERSION 3.1
+EXPORTS
+bar @3
+baz DATA
+qux @7 NONAME
+HeapAlloc = NTDLL.RtlAllocateHeap
+fwd = other.#2
+",
+            machine,
+        )
+        .unwrap();
+        let mut dll = Vec::new();
+        StubDll::from_def(def.clone(), machine)
+            .write_to(&mut dll)
+            .unwrap();
+        let read = ModuleDef::from_dll(&dll).unwrap();
+        assert_eq!(read.import_name, "foo.dll");
+        assert_eq!(read.major_image_version, 3);
+        assert_eq!(read.minor_image_version, 1);
+
+        let mut exports = read.exports.clone();
+        exports.sort_by_key(|e| e.ordinal);
+        let mut expected = def.exports.clone();
+        // Ordinals assigned by the stub DLL writer
+        expected[1].ordinal = 8;
+        expected[3].ordinal = 9;
+        expected[4].ordinal = 10;
+        expected[2].name = if machine == MachineType::I386 {
+            "_ord_7".to_string()
+        } else {
+            "ord_7".to_string()
+        };
+        expected.sort_by_key(|e| e.ordinal);
+        assert_eq!(exports, expected, "{:?}", machine);
+        assert_eq!(
+            exports[4].forward.as_ref().unwrap().target,
+            ForwardTarget::Ordinal(2)
+        );
+    }
+
+    let err = ModuleDef::from_dll(b"not a dll").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
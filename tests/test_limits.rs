@@ -0,0 +1,142 @@
+use std::io::Cursor;
+
+use implib::def::ModuleDef;
+use implib::{Error, Flavor, ImportLibrary, Limit, Limits, MachineType};
+
+const DEF: &str = "LIBRARY python39.dll
+EXPORTS
+Py_Initialize @1
+Py_Finalize @2
+Py_Version DATA
+";
+
+fn parse(def: &str, limits: Limits) -> Result<ModuleDef, Error> {
+    ModuleDef::parse_with_limits(def, MachineType::AMD64, &limits)
+}
+
+fn assert_exceeded<T: std::fmt::Debug>(result: Result<T, Error>, expected: Limit) {
+    match result {
+        Err(Error::LimitExceeded { limit, .. }) => assert_eq!(limit, expected),
+        result => panic!("expected {} to be exceeded, got {:?}", expected, result),
+    }
+}
+
+#[test]
+fn test_parse_limits() {
+    let def = parse(DEF, Limits::default()).unwrap();
+    assert_eq!(def.exports.len(), 3);
+    let limits = Limits {
+        max_def_size: Some(DEF.len()),
+        max_exports: Some(3),
+        max_name_len: Some("Py_Initialize".len()),
+        max_output_size: Some(0),
+    };
+    assert_eq!(parse(DEF, limits).unwrap().exports.len(), 3);
+
+    let limits = Limits {
+        max_def_size: Some(DEF.len() - 1),
+        ..Default::default()
+    };
+    let err = parse(DEF, limits).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("def size exceeds the limit of {}", DEF.len() - 1)
+    );
+
+    let limits = Limits {
+        max_exports: Some(2),
+        ..Default::default()
+    };
+    assert_exceeded(parse(DEF, limits), Limit::Exports);
+
+    // One giant identifier
+    let limits = Limits {
+        max_name_len: Some(64),
+        ..Default::default()
+    };
+    let def = format!("EXPORTS\n{}\n", "a".repeat(1 << 20));
+    assert_exceeded(parse(&def, limits), Limit::NameLength);
+    let def = format!("LIBRARY {}.dll\n", "a".repeat(64));
+    assert_exceeded(parse(&def, limits), Limit::NameLength);
+    let def = format!("EXPORTS\nfoo == {}\n", "a".repeat(65));
+    assert_exceeded(parse(&def, limits), Limit::NameLength);
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_write_limits(flavor: Flavor) {
+    let import_lib = ImportLibrary::new(DEF, MachineType::AMD64, flavor).unwrap();
+    let mut lib = Cursor::new(Vec::new());
+    import_lib.write_to(&mut lib).unwrap();
+    let size = lib.into_inner().len();
+
+    let limits = Limits {
+        max_output_size: Some(size),
+        ..Default::default()
+    };
+    let mut lib = Cursor::new(Vec::new());
+    import_lib
+        .clone()
+        .with_limits(limits)
+        .write_to(&mut lib)
+        .unwrap();
+    assert_eq!(lib.into_inner().len(), size);
+
+    let limits = Limits {
+        max_output_size: Some(size - 1),
+        ..Default::default()
+    };
+    let result = import_lib
+        .clone()
+        .with_limits(limits)
+        .write_to(&mut Cursor::new(Vec::new()));
+    assert_exceeded(result, Limit::OutputSize);
+
+    // Too many exports to fit fail before anything is written
+    let exports: String = (0..1000).map(|i| format!("func{}\n", i)).collect();
+    let def = format!("LIBRARY big.dll\nEXPORTS\n{}", exports);
+    let mut lib = Cursor::new(Vec::new());
+    let result = ImportLibrary::new(&def, MachineType::AMD64, flavor)
+        .unwrap()
+        .with_limits(limits)
+        .write_to(&mut lib);
+    assert_exceeded(result, Limit::OutputSize);
+    assert!(lib.get_ref().is_empty());
+
+    // Limits apply to hand-built module definitions as well
+    let mut def = ModuleDef::parse(DEF, MachineType::AMD64).unwrap();
+    def.exports[0].name = "a".repeat(1 << 20);
+    let limits = Limits {
+        max_name_len: Some(1024),
+        ..Default::default()
+    };
+    let result = ImportLibrary::from_def(def, MachineType::AMD64, flavor)
+        .with_limits(limits)
+        .write_to(&mut Cursor::new(Vec::new()));
+    assert_exceeded(result, Limit::NameLength);
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_write_limits_msvc() {
+    check_write_limits(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_write_limits_gnu() {
+    check_write_limits(Flavor::Gnu);
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_arm64x_export_limit() {
+    let import_lib = ImportLibrary::new_arm64x(DEF, DEF, Flavor::Msvc).unwrap();
+    let limits = Limits {
+        max_exports: Some(5),
+        ..Default::default()
+    };
+    let result = import_lib
+        .with_limits(limits)
+        .write_to(&mut Cursor::new(Vec::new()));
+    assert_exceeded(result, Limit::Exports);
+}
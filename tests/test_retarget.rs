@@ -0,0 +1,239 @@
+use std::io::Cursor;
+
+use object::read::archive::ArchiveFile;
+
+use implib::def::ModuleDef;
+use implib::inspect::ImportLibraryInfo;
+use implib::{Flavor, ImportLibrary, MachineType, UmbrellaImportLibrary, WriteOptions};
+
+const DEF: &str = "LIBRARY python39.dll
+EXPORTS
+Py_Initialize @1
+Py_Finalize @2
+PyObject_Free @7 NONAME
+Py_Version DATA
+PyWin_Callback@8
+";
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn write(import_lib: ImportLibrary) -> Vec<u8> {
+    let mut lib = Cursor::new(Vec::new());
+    import_lib.write_to(&mut lib).unwrap();
+    lib.into_inner()
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn retarget(data: &[u8], dll_name: &str, options: &WriteOptions) -> Vec<u8> {
+    let mut lib = Cursor::new(Vec::new());
+    ImportLibrary::retarget(data, dll_name, &mut lib, options).unwrap();
+    lib.into_inner()
+}
+
+/// Check that `retargeted` is `original` importing from `dll_name`
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn assert_retargeted(original: &[u8], retargeted: &[u8], dll_name: &str) {
+    let original = ImportLibraryInfo::parse(original).unwrap();
+    let retargeted = ImportLibraryInfo::parse(retargeted).unwrap();
+    let dll_names: Vec<_> = retargeted
+        .members
+        .iter()
+        .filter_map(|member| member.dll_name.as_deref())
+        .collect();
+    assert!(!dll_names.is_empty());
+    assert!(
+        dll_names.iter().all(|name| *name == dll_name),
+        "{:?}",
+        dll_names
+    );
+
+    let imports = |info: &ImportLibraryInfo| {
+        info.members
+            .iter()
+            .filter(|member| member.import_type.is_some())
+            .map(|member| {
+                (
+                    member.symbols.clone(),
+                    member.machine,
+                    member.hint,
+                    member.name_type,
+                    member.import_name.clone(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(imports(&original), imports(&retargeted));
+    let def = retargeted.to_module_def();
+    assert_eq!(def.import_name, dll_name);
+    assert_eq!(def.exports, original.to_module_def().exports);
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_retarget(flavor: Flavor) {
+    for machine in [
+        MachineType::AMD64,
+        MachineType::I386,
+        MachineType::ARMNT,
+        MachineType::ARM64,
+    ] {
+        let original = write(ImportLibrary::new(DEF, machine, flavor).unwrap());
+        // Shorter and longer names than the original
+        for dll_name in ["py.dll", "python39_vendored_build.dll"] {
+            let retargeted = retarget(&original, dll_name, &WriteOptions::default());
+            assert_retargeted(&original, &retargeted, dll_name);
+        }
+    }
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_retarget_msvc() {
+    check_retarget(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_retarget_gnu() {
+    check_retarget(Flavor::Gnu);
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_retarget_arm64x() {
+    let native = "LIBRARY python39.dll\nEXPORTS\nPy_Native\n";
+    let original = write(ImportLibrary::new_arm64x(DEF, native, Flavor::Msvc).unwrap());
+    let retargeted = retarget(&original, "py.dll", &WriteOptions::default());
+    assert_retargeted(&original, &retargeted, "py.dll");
+    let info = ImportLibraryInfo::parse(&retargeted).unwrap();
+    assert!(info
+        .members
+        .iter()
+        .any(|member| member.machine == Some(MachineType::ARM64EC)));
+    assert!(info
+        .members
+        .iter()
+        .any(|member| member.machine == Some(MachineType::ARM64)));
+}
+
+/// Retargeting is the same as generating with the new name
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_retarget_matches_fresh_library(flavor: Flavor) {
+    let original = write(ImportLibrary::new(DEF, MachineType::AMD64, flavor).unwrap());
+    let def = DEF.replace("python39.dll", "py.dll");
+    let retargeted = retarget(&original, "py.dll", &WriteOptions::default());
+    let fresh = write(ImportLibrary::new(&def, MachineType::AMD64, flavor).unwrap());
+    assert_eq!(
+        ImportLibraryInfo::parse(&retargeted).unwrap(),
+        ImportLibraryInfo::parse(&fresh).unwrap(),
+        "{:?}",
+        flavor
+    );
+
+    // With the member headers and GNU names of the options
+    let options = WriteOptions {
+        mtime: 1_700_000_000,
+        uid: 1000,
+        gid: 1000,
+        mode: 0o600,
+        gnu_member_prefix: Some("py_".to_string()),
+        gnu_symbol_stem: Some("py".to_string()),
+        ..Default::default()
+    };
+    let retargeted = retarget(&original, "py.dll", &options);
+    let fresh = write(
+        ImportLibrary::new(&def, MachineType::AMD64, flavor)
+            .unwrap()
+            .with_options(options.clone()),
+    );
+    assert_eq!(
+        ImportLibraryInfo::parse(&retargeted).unwrap(),
+        ImportLibraryInfo::parse(&fresh).unwrap(),
+        "{:?}",
+        flavor
+    );
+    let archive = ArchiveFile::parse(&*retargeted).unwrap();
+    for member in archive.members() {
+        let member = member.unwrap();
+        if member.name().starts_with(b"/") {
+            continue;
+        }
+        assert_eq!(member.date(), Some(options.mtime));
+        assert_eq!(member.uid(), Some(1000));
+        assert_eq!(member.gid(), Some(1000));
+        assert_eq!(member.mode(), Some(0o600));
+    }
+
+    // Members of a library written with custom GNU names are all renamed
+    let retargeted = retarget(&fresh, "x.dll", &WriteOptions::default());
+    let def = DEF.replace("python39.dll", "x.dll");
+    let fresh = write(ImportLibrary::new(&def, MachineType::AMD64, flavor).unwrap());
+    assert_eq!(
+        ImportLibraryInfo::parse(&retargeted).unwrap(),
+        ImportLibraryInfo::parse(&fresh).unwrap(),
+        "{:?}",
+        flavor
+    );
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_retarget_matches_fresh_library_msvc() {
+    check_retarget_matches_fresh_library(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_retarget_matches_fresh_library_gnu() {
+    check_retarget_matches_fresh_library(Flavor::Gnu);
+}
+
+#[test]
+fn test_retarget_errors() {
+    let options = WriteOptions::default();
+    let mut lib = Cursor::new(Vec::new());
+    for dll_name in ["", "a\0b.dll"] {
+        let err = ImportLibrary::retarget(b"!<arch>\n", dll_name, &mut lib, &options).unwrap_err();
+        assert!(err.to_string().starts_with("invalid DLL name"), "{}", err);
+    }
+
+    let err = ImportLibrary::retarget(b"!<arch>\n", "py.dll", &mut lib, &options).unwrap_err();
+    assert_eq!(err.to_string(), "not an import library");
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_retarget_several_dlls(flavor: Flavor) {
+    let defs = ["a.dll", "b.dll"]
+        .iter()
+        .map(|name| {
+            ModuleDef::parse(
+                &format!("LIBRARY {}\nEXPORTS\n{}_func\n", name, &name[..1]),
+                MachineType::AMD64,
+            )
+            .unwrap()
+        })
+        .collect();
+    let mut umbrella = Cursor::new(Vec::new());
+    UmbrellaImportLibrary::new(defs, MachineType::AMD64, flavor)
+        .write_to(&mut umbrella)
+        .unwrap();
+    let mut lib = Cursor::new(Vec::new());
+    let err = ImportLibrary::retarget(
+        umbrella.get_ref(),
+        "py.dll",
+        &mut lib,
+        &WriteOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "import library references several DLLs");
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_retarget_several_dlls_msvc() {
+    check_retarget_several_dlls(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_retarget_several_dlls_gnu() {
+    check_retarget_several_dlls(Flavor::Gnu);
+}
@@ -0,0 +1,106 @@
+use implib::def::ModuleDef;
+use implib::inspect::ImportLibraryInfo;
+use implib::{Error, Flavor, ImportLibrary, MachineType};
+
+const SPEC: &str = r#"
+# kernel32 excerpt
+@ stdcall CreateFileW(ptr long long ptr long long long)
+@ stdcall -arch=win32 GetTickCount64Hi(int64 double) MyGetTickCount
+@ cdecl -norelay sprintf(ptr str) MSVCRT_sprintf
+@ varargs -private wsprintfA(ptr str)
+12 stdcall -noname SetLastError(long)
+@ fastcall -arch=i386 InterlockedIncrement(ptr)
+@ stdcall HeapAlloc(long long long) ntdll.RtlAllocateHeap
+@ extern -arch=!i386 errno
+@ stub GetUserDefaultGeoName
+"#;
+
+#[test]
+fn test_parse_spec_i386() {
+    let def = ModuleDef::parse_spec(SPEC, MachineType::I386).unwrap();
+    assert_eq!(def.import_name, "");
+    // The .def file winebuild writes for it
+    let expected = ModuleDef::parse(
+        "EXPORTS
+CreateFileW@28
+GetTickCount64Hi@16 = MyGetTickCount@16
+sprintf = MSVCRT_sprintf
+wsprintfA PRIVATE
+SetLastError@4 @12 NONAME
+@InterlockedIncrement@4
+HeapAlloc@12 = ntdll.RtlAllocateHeap
+GetUserDefaultGeoName
+",
+        MachineType::I386,
+    )
+    .unwrap();
+    assert_eq!(def.exports, expected.exports);
+}
+
+#[test]
+fn test_parse_spec_amd64() {
+    let def = ModuleDef::parse_spec(SPEC, MachineType::AMD64).unwrap();
+    let expected = ModuleDef::parse(
+        "EXPORTS
+CreateFileW
+sprintf = MSVCRT_sprintf
+wsprintfA PRIVATE
+SetLastError @12 NONAME
+HeapAlloc = ntdll.RtlAllocateHeap
+errno DATA
+GetUserDefaultGeoName
+",
+        MachineType::AMD64,
+    )
+    .unwrap();
+    assert_eq!(def.exports, expected.exports);
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_spec_import_library(flavor: Flavor) {
+    let mut def = ModuleDef::parse_spec(SPEC, MachineType::I386).unwrap();
+    def.import_name = "kernel32.dll".to_string();
+    let mut lib = std::io::Cursor::new(Vec::new());
+    ImportLibrary::from_def(def, MachineType::I386, flavor)
+        .write_to(&mut lib)
+        .unwrap();
+    let info = ImportLibraryInfo::parse(lib.get_ref()).unwrap();
+    let symbols: Vec<_> = info
+        .members
+        .iter()
+        .flat_map(|member| &member.symbols)
+        .collect();
+    assert!(symbols.iter().any(|s| *s == "__imp_CreateFileW@28"));
+    assert!(symbols.iter().any(|s| *s == "@InterlockedIncrement@4"));
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_parse_spec_import_library_msvc() {
+    check_spec_import_library(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_parse_spec_import_library_gnu() {
+    check_spec_import_library(Flavor::Gnu);
+}
+
+#[test]
+fn test_parse_spec_errors() {
+    let err = ModuleDef::parse_spec("@ stdcall Foo(ptr)\n\n@ stdcall Bar(", MachineType::AMD64)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(matches!(
+        err,
+        Error::Parse {
+            line: 3,
+            column: 1,
+            ..
+        }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "line 3, column 1: Bar: unterminated argument list"
+    );
+}
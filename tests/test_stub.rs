@@ -0,0 +1,168 @@
+#![cfg(feature = "stub")]
+
+use implib::{MachineType, StubDll};
+use object::pe::*;
+use object::read::pe::{ExportTarget, ImageNtHeaders, ImageOptionalHeader, PeFile32, PeFile64};
+use object::LittleEndian as LE;
+
+const DEF: &str = "LIBRARY stub.dll
+BASE = 536870912
+STACKSIZE 2097152 , 8192
+HEAPSIZE 3145728 , 12288
+VERSION 2.5
+EXPORTS
+foo @3
+bar
+baz @5 NONAME
+qux DATA
+fwd = other.target
+fwd2 = other.#7
+";
+
+fn write(def: &str, machine: MachineType) -> Vec<u8> {
+    let mut dll = Vec::new();
+    StubDll::new(def, machine)
+        .unwrap()
+        .write_to(&mut dll)
+        .unwrap();
+    dll
+}
+
+/// Exports as (ordinal, name, target) sorted by ordinal
+fn exports<Pe: ImageNtHeaders>(
+    file: &object::read::pe::PeFile<'_, Pe>,
+) -> Vec<(u32, Option<String>, String)> {
+    let table = file.export_table().unwrap().unwrap();
+    let mut exports: Vec<_> = table
+        .exports()
+        .unwrap()
+        .into_iter()
+        // Skip unused ordinals
+        .filter(|export| !matches!(export.target, ExportTarget::Address(0)))
+        .map(|export| {
+            let name = export
+                .name
+                .map(|name| String::from_utf8(name.to_vec()).unwrap());
+            let target = match export.target {
+                ExportTarget::Address(_) => "address".to_string(),
+                ExportTarget::ForwardByName(lib, name) => format!(
+                    "{}.{}",
+                    String::from_utf8_lossy(lib),
+                    String::from_utf8_lossy(name)
+                ),
+                ExportTarget::ForwardByOrdinal(lib, ordinal) => {
+                    format!("{}.#{}", String::from_utf8_lossy(lib), ordinal)
+                }
+            };
+            (export.ordinal, name, target)
+        })
+        .collect();
+    exports.sort();
+    exports
+}
+
+fn expected() -> Vec<(u32, Option<String>, String)> {
+    let named =
+        |ordinal, name: &str, target: &str| (ordinal, Some(name.to_string()), target.to_string());
+    vec![
+        named(3, "foo", "address"),
+        (5, None, "address".to_string()),
+        named(6, "bar", "address"),
+        named(7, "qux", "address"),
+        named(8, "fwd", "other.target"),
+        named(9, "fwd2", "other.#7"),
+    ]
+}
+
+#[test]
+fn test_stub_amd64() {
+    let dll = write(DEF, MachineType::AMD64);
+    let file = PeFile64::parse(&*dll).unwrap();
+    let nt = file.nt_headers();
+    assert_eq!(nt.file_header().machine.get(LE), IMAGE_FILE_MACHINE_AMD64);
+    assert_ne!(nt.file_header().characteristics.get(LE) & IMAGE_FILE_DLL, 0);
+    let opt = nt.optional_header();
+    assert_eq!(opt.image_base(), 0x2000_0000);
+    assert_eq!(opt.size_of_stack_reserve(), 0x20_0000);
+    assert_eq!(opt.size_of_stack_commit(), 0x2000);
+    assert_eq!(opt.size_of_heap_reserve(), 0x30_0000);
+    assert_eq!(opt.size_of_heap_commit(), 0x3000);
+    assert_eq!(opt.major_image_version(), 2);
+    assert_eq!(opt.minor_image_version(), 5);
+    assert_eq!(exports(&file), expected());
+
+    let table = file.export_table().unwrap().unwrap();
+    let name = table.directory().name.get(LE);
+    assert_eq!(table.name_from_pointer(name).unwrap(), b"stub.dll");
+    // Data exports live in a writable section, code in an executable one
+    let section = |rva: u32| {
+        file.section_table()
+            .section_containing(rva)
+            .unwrap()
+            .characteristics
+            .get(LE)
+    };
+    let address = |ordinal| table.address_by_ordinal(ordinal).unwrap();
+    assert_ne!(section(address(3)) & IMAGE_SCN_MEM_EXECUTE, 0);
+    assert_ne!(section(address(7)) & IMAGE_SCN_MEM_WRITE, 0);
+}
+
+#[test]
+fn test_stub_i386() {
+    let dll = write(
+        "LIBRARY stub.dll\nEXPORTS\nfoo\nbar@8\nfwd = other.target\n",
+        MachineType::I386,
+    );
+    let file = PeFile32::parse(&*dll).unwrap();
+    let nt = file.nt_headers();
+    assert_eq!(nt.file_header().machine.get(LE), IMAGE_FILE_MACHINE_I386);
+    assert_eq!(nt.optional_header().image_base(), 0x1000_0000);
+    let names: Vec<_> = exports(&file)
+        .into_iter()
+        .map(|(_, name, target)| (name.unwrap(), target))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("foo".to_string(), "address".to_string()),
+            ("bar@8".to_string(), "address".to_string()),
+            ("fwd".to_string(), "other.target".to_string()),
+        ]
+    );
+    // stdcall stub pops its arguments: xor eax, eax; ret 8
+    let table = file.export_table().unwrap().unwrap();
+    let rva = table.address_by_ordinal(2).unwrap();
+    let offset = file.section_table().pe_file_range_at(rva).unwrap().0 as usize;
+    assert_eq!(dll[offset..offset + 5], [0x31, 0xc0, 0xc2, 8, 0]);
+}
+
+#[test]
+fn test_stub_arm() {
+    for machine in [
+        MachineType::ARM64,
+        MachineType::ARM64EC,
+        MachineType::ARM64X,
+    ] {
+        let dll = write(DEF, machine);
+        let file = PeFile64::parse(&*dll).unwrap();
+        assert_eq!(exports(&file), expected());
+    }
+    let dll = write(DEF, MachineType::ARMNT);
+    let file = PeFile32::parse(&*dll).unwrap();
+    assert_eq!(exports(&file), expected());
+}
+
+#[test]
+fn test_stub_errors() {
+    let mut dll = Vec::new();
+    let err = StubDll::new("EXPORTS\nfoo @1\nbar @1\n", MachineType::AMD64)
+        .unwrap()
+        .write_to(&mut dll)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "duplicate ordinal: 1");
+    let err = StubDll::new("EXPORTS\nfoo\nfoo DATA\n", MachineType::AMD64)
+        .unwrap()
+        .write_to(&mut dll)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "duplicate export: foo");
+}
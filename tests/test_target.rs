@@ -0,0 +1,87 @@
+use implib::{Error, Flavor, MachineType};
+
+#[test]
+fn test_from_target_triple() {
+    for (triple, machine, flavor) in [
+        ("x86_64-pc-windows-msvc", MachineType::AMD64, Flavor::Msvc),
+        ("x86_64-pc-windows-gnu", MachineType::AMD64, Flavor::Gnu),
+        ("x86_64-pc-windows-gnullvm", MachineType::AMD64, Flavor::Gnu),
+        ("x86_64-uwp-windows-msvc", MachineType::AMD64, Flavor::Msvc),
+        ("i686-pc-windows-msvc", MachineType::I386, Flavor::Msvc),
+        ("i586-pc-windows-msvc", MachineType::I386, Flavor::Msvc),
+        ("i686-w64-mingw32", MachineType::I386, Flavor::Gnu),
+        ("thumbv7a-pc-windows-msvc", MachineType::ARMNT, Flavor::Msvc),
+        ("armv7-w64-mingw32", MachineType::ARMNT, Flavor::Gnu),
+        ("aarch64-pc-windows-msvc", MachineType::ARM64, Flavor::Msvc),
+        (
+            "aarch64-pc-windows-gnullvm",
+            MachineType::ARM64,
+            Flavor::Gnu,
+        ),
+        (
+            "arm64ec-pc-windows-msvc",
+            MachineType::ARM64EC,
+            Flavor::Msvc,
+        ),
+    ] {
+        assert_eq!(
+            MachineType::from_target_triple(triple).unwrap(),
+            machine,
+            "{}",
+            triple
+        );
+        assert_eq!(
+            Flavor::from_target_triple(triple).unwrap(),
+            flavor,
+            "{}",
+            triple
+        );
+    }
+}
+
+#[test]
+fn test_from_target_triple_errors() {
+    for (triple, err) in [
+        (
+            "x86_64-unknown-linux-gnu",
+            "not a Windows target triple: x86_64-unknown-linux-gnu",
+        ),
+        ("x86_64", "not a Windows target triple: x86_64"),
+        (
+            "riscv64-pc-windows-msvc",
+            "unsupported architecture riscv64 in target triple: riscv64-pc-windows-msvc",
+        ),
+    ] {
+        let err_msg = MachineType::from_target_triple(triple)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err_msg, err);
+    }
+    let err = MachineType::from_target_triple("riscv64-pc-windows-msvc").unwrap_err();
+    assert!(matches!(err, Error::Unsupported(_)), "{:?}", err);
+    let err = Flavor::from_target_triple("x86_64-pc-windows-itanium").unwrap_err();
+    assert!(matches!(err, Error::Unsupported(_)), "{:?}", err);
+    assert_eq!(
+        err.to_string(),
+        "unsupported environment itanium in target triple: x86_64-pc-windows-itanium"
+    );
+}
+
+#[test]
+fn test_machine_names() {
+    for machine in [
+        MachineType::I386,
+        MachineType::AMD64,
+        MachineType::ARMNT,
+        MachineType::ARM64,
+        MachineType::ARM64EC,
+        MachineType::ARM64X,
+    ] {
+        assert_eq!(machine.to_string().parse::<MachineType>().unwrap(), machine);
+    }
+    assert_eq!(MachineType::AMD64.to_string(), "i386:x86-64");
+    assert_eq!(
+        "x86_64".parse::<MachineType>().unwrap_err().to_string(),
+        "unknown machine: x86_64"
+    );
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use implib::def::ModuleDef;
+use implib::MachineType;
+
+const TEMPLATE: &str = r#"; python3.def.in
+#if !defined(PY_DLL_NAME)
+#  define PY_DLL_NAME "python39.dll"
+#endif
+LIBRARY PY_DLL_NAME
+EXPORTS
+  Py_Initialize
+#ifdef _WIN64
+  PyWin64_Only
+#elif defined(_M_IX86) && _M_IX86 >= 600
+  PyX86_Only
+#else
+  PyArm_Only
+#endif
+#if defined(Py_DEBUG)
+  _Py_RefTotal DATA
+#endif
+"#;
+
+fn names(def: &ModuleDef) -> Vec<&str> {
+    def.exports.iter().map(|e| e.name.as_str()).collect()
+}
+
+#[test]
+fn test_parse_template() {
+    let defines = HashMap::new();
+    let def = ModuleDef::parse_template(TEMPLATE, MachineType::AMD64, &defines).unwrap();
+    assert_eq!(def.import_name, "python39.dll");
+    assert_eq!(names(&def), ["Py_Initialize", "PyWin64_Only"]);
+
+    let def = ModuleDef::parse_template(TEMPLATE, MachineType::I386, &defines).unwrap();
+    assert_eq!(names(&def), ["_Py_Initialize", "_PyX86_Only"]);
+
+    let def = ModuleDef::parse_template(TEMPLATE, MachineType::ARMNT, &defines).unwrap();
+    assert_eq!(names(&def), ["Py_Initialize", "PyArm_Only"]);
+
+    let defines = [
+        ("Py_DEBUG", ""),
+        ("PY_DLL_NAME", "\"python39_d.dll\""),
+        ("_WIN64", "0"),
+    ]
+    .iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+    let def = ModuleDef::parse_template(TEMPLATE, MachineType::ARM64, &defines).unwrap();
+    assert_eq!(def.import_name, "python39_d.dll");
+    assert_eq!(
+        names(&def),
+        ["Py_Initialize", "PyWin64_Only", "_Py_RefTotal"]
+    );
+    assert!(def.exports[2].data);
+}
+
+#[test]
+fn test_preprocess() {
+    let mut defines = HashMap::new();
+    defines.insert("A".to_string(), "1".to_string());
+    let output = ModuleDef::preprocess(
+        "#if A\nfoo\n#else\nbar\n#endif\n#undef A\n#ifndef A\nA\n#endif",
+        &defines,
+    )
+    .unwrap();
+    // Line numbers are kept for the def parser's errors
+    assert_eq!(output, "\nfoo\n\n\n\n\n\nA\n\n");
+}
+
+#[test]
+fn test_preprocess_errors() {
+    let defines = HashMap::new();
+    for (template, err) in [
+        ("#if 1\nfoo", "line 1, column 1: unterminated #if"),
+        ("foo\n#endif", "line 2, column 1: #endif without #if"),
+        ("#else", "line 1, column 1: #else without #if"),
+        (
+            "#if 1\n#else\n#elif 1",
+            "line 3, column 1: #elif after #else",
+        ),
+        ("#if 1\n#else\n#else", "line 3, column 1: #else after #else"),
+        (
+            "#include <foo.h>",
+            "line 1, column 1: unsupported directive: #include",
+        ),
+        (
+            "#define F(x) x",
+            "line 1, column 1: function-like macro not supported: F",
+        ),
+        (
+            "#ifdef 1",
+            "line 1, column 1: expected identifier, found: \"1\"",
+        ),
+        ("#error no ARM", "line 1, column 1: #error no ARM"),
+        ("#if (1", "line 1, column 1: expected ) in #if"),
+        ("foo\n  #if 1\n", "line 2, column 3: unterminated #if"),
+    ] {
+        let err_msg = ModuleDef::preprocess(template, &defines)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err_msg, err, "{}", template);
+    }
+    // Skipped groups aren't evaluated
+    assert!(ModuleDef::preprocess("#if 0\n#error\n#if (\n#endif\n#endif", &defines).is_ok());
+}
@@ -0,0 +1,207 @@
+use implib::def::{ModuleDef, Pattern, Transform, TransformReport};
+use implib::inspect::{ImportLibraryInfo, ImportType};
+use implib::{Flavor, ImportLibrary, MachineType};
+
+const DEF: &str = "LIBRARY python39.dll
+EXPORTS
+Py_Initialize
+Py_Finalize
+PyObject_Free
+_Py_Dealloc
+Py_Version DATA
+PyWin_Callback@8
+PyUnstable_Thing
+";
+
+fn transform() -> Transform {
+    Transform::new()
+        .include("Py*")
+        .include("_Py_*")
+        .exclude("PyUnstable_*")
+        .rename("Py_Finalize", "Py_Shutdown")
+        .rename_with(|name| {
+            name.strip_prefix("_Py_")
+                .map(|n| format!("Py_Private_{}", n))
+        })
+        .prefix("vnd_")
+        .force_data("PyObject_*")
+}
+
+/// Imports as (`__imp_` symbol, import name, import type)
+fn imports(data: &[u8]) -> Vec<(String, Option<String>, Option<ImportType>)> {
+    ImportLibraryInfo::parse(data)
+        .unwrap()
+        .members
+        .into_iter()
+        .filter_map(|member| {
+            let symbol = member
+                .symbols
+                .iter()
+                .find(|s| s.starts_with("__imp_") && !s.contains("NULL_THUNK"))?
+                .clone();
+            Some((symbol, member.import_name, member.import_type))
+        })
+        .collect()
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_transform_import_library(flavor: Flavor) {
+    for machine in [MachineType::AMD64, MachineType::I386] {
+        let mut import_lib = ImportLibrary::new(DEF, machine, flavor).unwrap();
+        let report = import_lib.transform(&transform());
+        assert_eq!(report.removed, ["PyUnstable_Thing"]);
+        assert_eq!(report.forced_data, ["PyObject_Free"]);
+
+        let mut lib = std::io::Cursor::new(Vec::new());
+        import_lib.write_to(&mut lib).unwrap();
+        let imports = imports(lib.get_ref());
+        let prefix = if machine == MachineType::I386 {
+            "_"
+        } else {
+            ""
+        };
+        let expected = [
+            ("vnd_Py_Initialize", "Py_Initialize", ImportType::Code),
+            ("vnd_Py_Shutdown", "Py_Finalize", ImportType::Code),
+            ("vnd_PyObject_Free", "PyObject_Free", ImportType::Data),
+            ("vnd_Py_Private_Dealloc", "_Py_Dealloc", ImportType::Code),
+            ("vnd_Py_Version", "Py_Version", ImportType::Data),
+        ];
+        for (symbol, import_name, import_type) in expected {
+            let symbol = format!("__imp_{}{}", prefix, symbol);
+            assert!(
+                imports.contains(&(
+                    symbol.clone(),
+                    Some(import_name.to_string()),
+                    Some(import_type)
+                )),
+                "{:?} {:?}: {} missing from {:?}",
+                flavor,
+                machine,
+                symbol,
+                imports
+            );
+        }
+        // Decorations stay at the ends
+        let stdcall = imports
+            .iter()
+            .find(|(symbol, _, _)| symbol.contains("Callback"))
+            .unwrap();
+        assert_eq!(stdcall.0, "__imp_vnd_PyWin_Callback@8");
+        assert_eq!(stdcall.1.as_deref(), Some("PyWin_Callback@8"));
+        assert_eq!(imports.len(), 6);
+    }
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_transform_import_library_msvc() {
+    check_transform_import_library(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_transform_import_library_gnu() {
+    check_transform_import_library(Flavor::Gnu);
+}
+
+#[cfg(any(feature = "msvc", feature = "gnu"))]
+fn check_transform_keeps_import_names(flavor: Flavor) {
+    let def = "LIBRARY foo.dll\nEXPORTS\nfoo\nbar@4\n_baz@4\n@qux@8\n";
+    for machine in [MachineType::AMD64, MachineType::I386] {
+        let import_names = |transform: &Transform| {
+            let mut import_lib = ImportLibrary::new(def, machine, flavor).unwrap();
+            import_lib.transform(transform);
+            let mut lib = std::io::Cursor::new(Vec::new());
+            import_lib.write_to(&mut lib).unwrap();
+            imports(lib.get_ref())
+                .into_iter()
+                .map(|(_, import_name, _)| import_name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            import_names(&Transform::new().prefix("vnd_")),
+            import_names(&Transform::new()),
+            "{:?} {:?}",
+            flavor,
+            machine
+        );
+    }
+}
+
+#[cfg(feature = "msvc")]
+#[test]
+fn test_transform_keeps_import_names_msvc() {
+    check_transform_keeps_import_names(Flavor::Msvc);
+}
+
+#[cfg(feature = "gnu")]
+#[test]
+fn test_transform_keeps_import_names_gnu() {
+    check_transform_keeps_import_names(Flavor::Gnu);
+}
+
+#[test]
+fn test_transform_report() {
+    let mut def = ModuleDef::parse(DEF, MachineType::I386).unwrap();
+    let report = def.transform(&transform(), MachineType::I386, Flavor::Msvc);
+    assert_eq!(
+        report.renamed,
+        [
+            ("Py_Initialize", "vnd_Py_Initialize"),
+            ("Py_Finalize", "vnd_Py_Shutdown"),
+            ("PyObject_Free", "vnd_PyObject_Free"),
+            ("_Py_Dealloc", "vnd_Py_Private_Dealloc"),
+            ("Py_Version", "vnd_Py_Version"),
+            ("PyWin_Callback@8", "vnd_PyWin_Callback@8"),
+        ]
+        .map(|(old, new)| (old.to_string(), new.to_string()))
+    );
+    assert_eq!(
+        report.to_string().lines().take(3).collect::<Vec<_>>(),
+        [
+            "removed\tPyUnstable_Thing",
+            "renamed\tPy_Initialize\tvnd_Py_Initialize",
+            "renamed\tPy_Finalize\tvnd_Py_Shutdown",
+        ]
+    );
+    assert_eq!(
+        report.to_string().lines().last(),
+        Some("data\tPyObject_Free")
+    );
+
+    // A no-op transform changes nothing
+    let mut def = ModuleDef::parse(DEF, MachineType::AMD64).unwrap();
+    let exports = def.exports.clone();
+    assert_eq!(
+        def.transform(&Transform::new(), MachineType::AMD64, Flavor::Msvc),
+        TransformReport::default()
+    );
+    assert_eq!(def.exports, exports);
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_transform_regex() {
+    let mut def = ModuleDef::parse(DEF, MachineType::AMD64).unwrap();
+    let transform = Transform::new()
+        .exclude(Pattern::regex("^Py_(Initialize|Finalize)$").unwrap())
+        .force_data(Pattern::regex("Version").unwrap());
+    let report = def.transform(&transform, MachineType::AMD64, Flavor::Msvc);
+    assert_eq!(report.removed, ["Py_Initialize", "Py_Finalize"]);
+    assert!(report.forced_data.is_empty());
+    assert!(Pattern::regex("(").is_err());
+}
+
+#[test]
+fn test_transform_glob_pattern() {
+    let mut def = ModuleDef::parse(DEF, MachineType::AMD64).unwrap();
+    let report = def.transform(
+        &Transform::new().include(Pattern::glob("Py_????????")),
+        MachineType::AMD64,
+        Flavor::Msvc,
+    );
+    assert_eq!(def.exports.len(), 1);
+    assert_eq!(def.exports[0].name, "Py_Finalize");
+    assert_eq!(report.removed.len(), 6);
+}